@@ -0,0 +1,65 @@
+use super::{insert_branch_target, InnerJumpTable, OperandPatches};
+use crate::binary::Symbol;
+use crate::errors::WCapstoneError;
+use capstone::arch::arm64::{Arm64Insn, Arm64OperandType};
+use capstone::prelude::*;
+use capstone::Insn;
+
+pub fn analyze_instructions_aarch64<'i, 's>(
+    symbols: &'s [Symbol<'s>],
+    cs: &Capstone,
+    instrs: &[&Insn<'i>],
+    jumps: &mut InnerJumpTable,
+    op_patches: &mut OperandPatches<'s>,
+) -> anyhow::Result<()> {
+    for (idx, instr) in instrs.iter().enumerate() {
+        if !aarch64_is_branch_insn(instr.id()) {
+            continue;
+        }
+
+        let detail = cs.insn_detail(instr).map_err(WCapstoneError)?;
+        let target = if let Some(target) = aarch64_get_branch_target(&detail) {
+            target
+        } else {
+            continue;
+        };
+
+        insert_branch_target(symbols, instrs, idx, target, jumps, op_patches);
+    }
+
+    Ok(())
+}
+
+/// Returns true for branches that encode their target as a PC relative immediate. Conditional
+/// branches (`b.cond`) share the same instruction ID as `b` and only differ in their condition
+/// code.
+fn aarch64_is_branch_insn(id: InsnId) -> bool {
+    use Arm64Insn::*;
+
+    matches!(
+        Arm64Insn::from(id.0),
+        ARM64_INS_B
+            | ARM64_INS_BL
+            | ARM64_INS_CBZ
+            | ARM64_INS_CBNZ
+            | ARM64_INS_TBZ
+            | ARM64_INS_TBNZ
+    )
+}
+
+fn aarch64_get_branch_target(detail: &InsnDetail<'_>) -> Option<u64> {
+    let arm64_detail = match detail.arch_detail() {
+        capstone::arch::ArchDetail::Arm64Detail(d) => d,
+        _ => return None,
+    };
+
+    // The target is always the last operand:
+    //      b/bl    <label>
+    //      cbz     <Rt>, <label>
+    //      tbz     <Rt>, #<imm>, <label>
+    // Capstone has already resolved the label into an absolute address for us.
+    match arm64_detail.operands().last()?.op_type {
+        Arm64OperandType::Imm(target) => Some(target as u64),
+        _ => None,
+    }
+}
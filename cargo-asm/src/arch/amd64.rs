@@ -1,52 +1,183 @@
-use super::{InnerJumpTable, OperandPatches};
-use crate::binary::Symbol;
+use super::{insert_branch_target, InnerJumpTable, OperandPatches};
+use crate::binary::{Binary, BinaryArch, Symbol};
 use crate::errors::WCapstoneError;
-use capstone::arch::x86::X86OperandType;
+use byteorder::{ByteOrder as _, LittleEndian};
+use capstone::arch::x86::{X86Insn, X86OperandType};
 use capstone::prelude::*;
 use capstone::Insn;
 
+/// Register holding an address computed by a call to one of the `__x86.get_pc_thunk.*` functions
+/// that 32-bit position independent code uses to find the GOT, e.g.
+///
+/// ```text
+/// call    __x86.get_pc_thunk.bx
+/// add     ebx, 0x1b2c
+/// call    dword ptr [ebx + 0x10]
+/// ```
+struct PicBase {
+    reg: RegId,
+    addr: u64,
+}
+
 pub fn analyze_instructions_amd64<'i, 's>(
-    symbols: &'s [Symbol<'s>],
+    binary: &'s Binary<'s>,
     cs: &Capstone,
-    instrs: &[Insn<'i>],
+    instrs: &[&Insn<'i>],
     jumps: &mut InnerJumpTable,
     op_patches: &mut OperandPatches<'s>,
 ) -> anyhow::Result<()> {
     use capstone::arch::x86::X86InsnGroup;
 
+    let mut pic_base: Option<PicBase> = None;
+
     for (idx, instr) in instrs.iter().enumerate() {
         let detail = cs.insn_detail(&instr).map_err(WCapstoneError)?;
 
         let mut target: Option<u64> = None;
 
-        if detail
+        let is_jump = detail
             .groups()
-            .any(|g| g == InsnGroupId(X86InsnGroup::X86_GRP_JUMP as u8))
-        {
-            target = amd64_get_jump_target(instr, &detail);
-        } else if detail
+            .iter()
+            .any(|&g| g == InsnGroupId(X86InsnGroup::X86_GRP_JUMP as u8));
+        let is_call = detail
             .groups()
-            .any(|g| g == InsnGroupId(X86InsnGroup::X86_GRP_CALL as u8))
-        {
+            .iter()
+            .any(|&g| g == InsnGroupId(X86InsnGroup::X86_GRP_CALL as u8));
+
+        if is_jump {
+            target = amd64_get_jump_target(instr, &detail);
+        } else if is_call {
             target = amd64_get_call_target(instr, &detail);
         }
 
-        if target.is_none() {
-            continue;
+        if binary.arch == BinaryArch::X86 {
+            if target.is_none() && (is_jump || is_call) {
+                target = pic_base
+                    .as_ref()
+                    .and_then(|pic| x86_get_pic_branch_target(binary, pic, &detail));
+            }
+            pic_base = x86_update_pic_base(pic_base, &binary.symbols, instr, &detail, target);
         }
-        let target: u64 = target.unwrap();
 
-        // If it's a regular inner jump, then we just display the address and move on.
-        if let Ok(target_index) = instrs.binary_search_by(|rhs| rhs.address().cmp(&target)) {
-            jumps.insert(idx, target_index);
-        } else if let Some(symbol) = symbols.iter().find(|sym| sym.addr == target) {
-            op_patches.insert(idx, symbol);
+        // Memory operands of indirect branches resolve to the address of the slot holding the
+        // target, which for calls to imported functions is a slot in the GOT.
+        if let Some(import_symbol) = target.and_then(|target| binary.import_symbol(target)) {
+            op_patches.insert(idx, import_symbol);
+        } else if let Some(target) = target {
+            insert_branch_target(&binary.symbols, instrs, idx, target, jumps, op_patches);
         }
     }
 
     Ok(())
 }
 
+/// Resolves a `call dword ptr [reg + disp]` where `reg` holds the address of the GOT by reading
+/// the target address out of the GOT slot. Slots of imported symbols are returned as is because
+/// they aren't filled in until the binary is loaded.
+fn x86_get_pic_branch_target(
+    binary: &Binary<'_>,
+    pic_base: &PicBase,
+    detail: &InsnDetail<'_>,
+) -> Option<u64> {
+    use capstone::arch::x86::X86Reg::X86_REG_INVALID;
+
+    let x86_detail = match detail.arch_detail() {
+        capstone::arch::ArchDetail::X86Detail(d) => d,
+        _ => return None,
+    };
+
+    let mut operands = x86_detail.operands();
+    let branch_operand = operands.next()?;
+    if operands.next().is_some() {
+        return None;
+    }
+
+    match branch_operand.op_type {
+        X86OperandType::Mem(op_mem)
+            if op_mem.base() == pic_base.reg && op_mem.index() == RegId(X86_REG_INVALID as _) =>
+        {
+            let slot_addr = pic_base.addr.wrapping_add(op_mem.disp() as u64) & 0xFFFF_FFFF;
+            if binary.import_symbol(slot_addr).is_some() {
+                return Some(slot_addr);
+            }
+            let slot = binary.data_at_addr(slot_addr, 4)?;
+            Some(LittleEndian::read_u32(slot) as u64)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the new state of the PIC base register after `instr` has been executed.
+fn x86_update_pic_base(
+    pic_base: Option<PicBase>,
+    symbols: &[Symbol<'_>],
+    instr: &Insn<'_>,
+    detail: &InsnDetail<'_>,
+    target: Option<u64>,
+) -> Option<PicBase> {
+    if let Some(reg) = target
+        .and_then(|target| symbols.iter().find(|sym| sym.addr == target))
+        .and_then(|sym| x86_pc_thunk_register(&sym.original_name))
+    {
+        return Some(PicBase {
+            reg,
+            addr: instr.address() + instr.bytes().len() as u64,
+        });
+    }
+
+    let mut pic_base = pic_base?;
+
+    let x86_detail = match detail.arch_detail() {
+        capstone::arch::ArchDetail::X86Detail(d) => d,
+        _ => return None,
+    };
+    let mut operands = x86_detail.operands().map(|op| op.op_type);
+    let (dest, src) = (operands.next(), operands.next());
+
+    if !matches!(dest, Some(X86OperandType::Reg(reg)) if reg == pic_base.reg) {
+        return Some(pic_base);
+    }
+
+    // Anything other than adding or subtracting an immediate from the register that we are
+    // tracking makes us lose track of its value.
+    match (X86Insn::from(instr.id().0), src) {
+        (X86Insn::X86_INS_ADD, Some(X86OperandType::Imm(imm))) => {
+            pic_base.addr = pic_base.addr.wrapping_add(imm as u64) & 0xFFFF_FFFF;
+            Some(pic_base)
+        }
+        (X86Insn::X86_INS_SUB, Some(X86OperandType::Imm(imm))) => {
+            pic_base.addr = pic_base.addr.wrapping_sub(imm as u64) & 0xFFFF_FFFF;
+            Some(pic_base)
+        }
+        (X86Insn::X86_INS_CMP, _) | (X86Insn::X86_INS_TEST, _) | (X86Insn::X86_INS_PUSH, _) => {
+            Some(pic_base)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the register that a PC thunk function loads the PC into, from the thunk's name.
+fn x86_pc_thunk_register(name: &str) -> Option<RegId> {
+    use capstone::arch::x86::X86Reg::*;
+
+    let suffix = name
+        .strip_prefix("__x86.get_pc_thunk.")
+        .or_else(|| name.strip_prefix("__i686.get_pc_thunk."))?;
+
+    let reg = match suffix {
+        "ax" => X86_REG_EAX,
+        "bx" => X86_REG_EBX,
+        "cx" => X86_REG_ECX,
+        "dx" => X86_REG_EDX,
+        "si" => X86_REG_ESI,
+        "di" => X86_REG_EDI,
+        "bp" => X86_REG_EBP,
+        _ => return None,
+    };
+
+    Some(RegId(reg as _))
+}
+
 fn amd64_is_call_opcode(opcode: &[u8]) -> bool {
     if opcode.is_empty() {
         return false;
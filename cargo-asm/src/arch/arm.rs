@@ -0,0 +1,59 @@
+use super::{insert_branch_target, InnerJumpTable, OperandPatches};
+use crate::binary::Symbol;
+use crate::errors::WCapstoneError;
+use capstone::arch::arm::{ArmInsn, ArmOperandType};
+use capstone::prelude::*;
+use capstone::Insn;
+
+/// Works for both ARM and Thumb instructions since they share the same instruction IDs and
+/// operand types in Capstone.
+pub fn analyze_instructions_arm<'i, 's>(
+    symbols: &'s [Symbol<'s>],
+    cs: &Capstone,
+    instrs: &[&Insn<'i>],
+    jumps: &mut InnerJumpTable,
+    op_patches: &mut OperandPatches<'s>,
+) -> anyhow::Result<()> {
+    for (idx, instr) in instrs.iter().enumerate() {
+        if !arm_is_branch_insn(instr.id()) {
+            continue;
+        }
+
+        let detail = cs.insn_detail(instr).map_err(WCapstoneError)?;
+        let target = if let Some(target) = arm_get_branch_target(&detail) {
+            target
+        } else {
+            continue;
+        };
+
+        insert_branch_target(symbols, instrs, idx, target, jumps, op_patches);
+    }
+
+    Ok(())
+}
+
+/// Returns true for branches that may encode their target as a PC relative immediate. `bx` and
+/// `blx` can also branch to a register, in which case there won't be a target.
+fn arm_is_branch_insn(id: InsnId) -> bool {
+    use ArmInsn::*;
+
+    matches!(
+        ArmInsn::from(id.0),
+        ARM_INS_B | ARM_INS_BL | ARM_INS_BLX | ARM_INS_CBZ | ARM_INS_CBNZ
+    )
+}
+
+fn arm_get_branch_target(detail: &InsnDetail<'_>) -> Option<u64> {
+    let arm_detail = match detail.arch_detail() {
+        capstone::arch::ArchDetail::ArmDetail(d) => d,
+        _ => return None,
+    };
+
+    // The target is always the last operand:
+    //      b/bl/blx    <label>
+    //      cbz         <Rn>, <label>
+    match arm_detail.operands().last()?.op_type {
+        ArmOperandType::Imm(target) => Some(target as u32 as u64),
+        _ => None,
+    }
+}
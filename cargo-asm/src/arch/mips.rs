@@ -0,0 +1,105 @@
+use super::{InnerJumpTable, OperandPatches};
+use crate::binary::Symbol;
+use crate::errors::WCapstoneError;
+use capstone::arch::mips::{MipsInsn, MipsOperand};
+use capstone::prelude::*;
+use capstone::Insn;
+
+pub fn analyze_instructions_mips<'i, 's>(
+    symbols: &'s [Symbol<'s>],
+    cs: &Capstone,
+    instrs: &[&Insn<'i>],
+    jumps: &mut InnerJumpTable,
+    op_patches: &mut OperandPatches<'s>,
+) -> anyhow::Result<()> {
+    use capstone::arch::mips::MipsInsnGroup;
+
+    for (idx, instr) in instrs.iter().enumerate() {
+        let detail = cs.insn_detail(instr).map_err(WCapstoneError)?;
+
+        let is_branch = detail.groups().iter().any(|&g| {
+            g == InsnGroupId(MipsInsnGroup::MIPS_GRP_JUMP as u8)
+                || g == InsnGroupId(MipsInsnGroup::MIPS_GRP_CALL as u8)
+        });
+        if !is_branch {
+            continue;
+        }
+
+        let target = if let Some(target) = mips_get_branch_target(&detail) {
+            target
+        } else {
+            continue;
+        };
+
+        if let Ok(target_index) = instrs.binary_search_by(|rhs| rhs.address().cmp(&target)) {
+            // The instruction in the delay slot is executed before control is transferred to
+            // the target, so the jump is drawn from there instead of from the branch itself.
+            let source = if mips_has_delay_slot(instr.id()) && idx + 1 < instrs.len() {
+                idx + 1
+            } else {
+                idx
+            };
+            jumps.insert(source, target_index);
+        } else if let Some(symbol) = symbols.iter().find(|sym| sym.addr == target) {
+            op_patches.insert(idx, symbol);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns false for the compact branches that were added in MIPS32/MIPS64 Release 6 and
+/// microMIPS. Every other branch and jump is followed by a delay slot.
+fn mips_has_delay_slot(id: InsnId) -> bool {
+    use MipsInsn::*;
+
+    !matches!(
+        MipsInsn::from(id.0),
+        MIPS_INS_BALC
+            | MIPS_INS_BC
+            | MIPS_INS_BEQC
+            | MIPS_INS_BEQZALC
+            | MIPS_INS_BEQZC
+            | MIPS_INS_BGEC
+            | MIPS_INS_BGEUC
+            | MIPS_INS_BGEZALC
+            | MIPS_INS_BGEZC
+            | MIPS_INS_BGTZALC
+            | MIPS_INS_BGTZC
+            | MIPS_INS_BLEZALC
+            | MIPS_INS_BLEZC
+            | MIPS_INS_BLTC
+            | MIPS_INS_BLTUC
+            | MIPS_INS_BLTZALC
+            | MIPS_INS_BLTZC
+            | MIPS_INS_BNEC
+            | MIPS_INS_BNEZALC
+            | MIPS_INS_BNEZC
+            | MIPS_INS_BNVC
+            | MIPS_INS_BOVC
+            | MIPS_INS_JALRC
+            | MIPS_INS_JIALC
+            | MIPS_INS_JIC
+            | MIPS_INS_JRC
+            | MIPS_INS_B16
+            | MIPS_INS_BEQZ16
+            | MIPS_INS_BNEZ16
+    )
+}
+
+fn mips_get_branch_target(detail: &InsnDetail<'_>) -> Option<u64> {
+    let mips_detail = match detail.arch_detail() {
+        capstone::arch::ArchDetail::MipsDetail(d) => d,
+        _ => return None,
+    };
+
+    // The target is always the last operand:
+    //      b/j/jal     <label>
+    //      beqz        <rs>, <label>
+    //      beq         <rs>, <rt>, <label>
+    // Register jumps (`jr`, `jalr`) don't have an immediate operand and are ignored.
+    match mips_detail.operands().last()? {
+        MipsOperand::Imm(target) => Some(target as u64),
+        _ => None,
+    }
+}
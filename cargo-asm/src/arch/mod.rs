@@ -1,10 +1,21 @@
+mod aarch64;
 mod amd64;
+mod arm;
+mod mips;
+mod ppc;
+mod riscv;
 
-use crate::binary::{BinaryArch, Symbol};
+use crate::binary::{Binary, BinaryArch, BinaryBits, BinaryEndian, Symbol};
+use crate::errors::{CargoAsmError, WCapstoneError};
+use aarch64::*;
 use amd64::*;
+use arm::*;
 use capstone::prelude::*;
 use capstone::Insn;
-use std::ops::RangeInclusive;
+use mips::*;
+use ppc::*;
+use riscv::*;
+use std::ops::{Range, RangeInclusive};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct InnerJump {
@@ -55,6 +66,11 @@ impl InnerJumpTable {
         })
     }
 
+    /// Removes the jumps that don't match the predicate.
+    pub fn retain<F: FnMut(&InnerJump) -> bool>(&mut self, f: F) {
+        self.jumps.retain(f);
+    }
+
     pub fn max_display_offset(&self) -> usize {
         self.max_display_offset
     }
@@ -116,14 +132,59 @@ impl<'s> OperandPatches<'s> {
         self.patches[index] = Some(symbol);
     }
 
-    pub fn get(&self, index: usize) -> Option<&str> {
+    /// Returns the operands of the instruction at `index` with the address of its target replaced
+    /// by the name of the symbol that it points to. The address is searched for in the operands
+    /// instead of relying on its position so that this works for every assembly syntax. If the
+    /// address isn't found the symbol's name replaces all of the operands. The hashes of Rust
+    /// symbol names are only shown if `hashes` is true.
+    pub fn patch_operands(&self, index: usize, op_str: &str, hashes: bool) -> Option<String> {
         // FIXME make using the short/long name configurable.
-        self.patches
-            .get(index)
-            .and_then(|sym| sym.as_ref().map(|s| s.short_demangled_name()))
+        let symbol = self.patches.get(index).and_then(|sym| sym.as_ref())?;
+        let name = symbol.display_short_name(hashes);
+
+        if let Some(range) = find_address_in_operands(op_str, symbol.addr) {
+            let mut patched = String::with_capacity(op_str.len() - range.len() + name.len());
+            patched.push_str(&op_str[..range.start]);
+            patched.push_str(&name);
+            patched.push_str(&op_str[range.end..]);
+            Some(patched)
+        } else {
+            Some(name.to_string())
+        }
     }
 }
 
+/// Finds an address written as an immediate in a string of operands, either in the usual `0x`
+/// form or in the `h` suffixed form used by MASM syntax.
+pub fn find_address_in_operands(op_str: &str, addr: u64) -> Option<Range<usize>> {
+    let masm = format!("{:x}h", addr);
+    let candidates = [
+        format!("0x{:x}", addr),
+        // MASM puts a leading zero in front of numbers that would otherwise start with a letter.
+        if masm.starts_with(|c: char| c.is_ascii_digit()) {
+            masm
+        } else {
+            format!("0{}", masm)
+        },
+    ];
+
+    for candidate in candidates.iter() {
+        for (start, _) in op_str.match_indices(candidate.as_str()) {
+            let end = start + candidate.len();
+            let is_boundary =
+                |c: Option<char>| c.map(|c| !c.is_ascii_alphanumeric()).unwrap_or(true);
+
+            if is_boundary(op_str[..start].chars().next_back())
+                && is_boundary(op_str[end..].chars().next())
+            {
+                return Some(start..end);
+            }
+        }
+    }
+
+    None
+}
+
 fn do_ranges_overlap(a: RangeInclusive<usize>, b: RangeInclusive<usize>) -> bool {
     let a_top = std::cmp::min(*a.start(), *a.end());
     let b_bot = std::cmp::max(*b.start(), *b.end());
@@ -140,20 +201,189 @@ fn do_ranges_overlap(a: RangeInclusive<usize>, b: RangeInclusive<usize>) -> bool
     true
 }
 
-pub fn analyze_instructions<'i, 's>(
-    symbols: &'s [Symbol<'s>],
+/// The assembly syntax used to display x86 instructions. Other architectures only have a single
+/// syntax and ignore this.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AsmSyntax {
+    Intel,
+    Att,
+    Masm,
+}
+
+impl Default for AsmSyntax {
+    fn default() -> Self {
+        AsmSyntax::Intel
+    }
+}
+
+impl From<AsmSyntax> for arch::x86::ArchSyntax {
+    fn from(syntax: AsmSyntax) -> Self {
+        match syntax {
+            AsmSyntax::Intel => arch::x86::ArchSyntax::Intel,
+            AsmSyntax::Att => arch::x86::ArchSyntax::Att,
+            AsmSyntax::Masm => arch::x86::ArchSyntax::Masm,
+        }
+    }
+}
+
+/// Creates a Capstone instance that can decode instructions for the given architecture.
+pub fn create_capstone(
     arch: BinaryArch,
+    bits: BinaryBits,
+    endian: BinaryEndian,
+    syntax: AsmSyntax,
+) -> anyhow::Result<Capstone> {
+    let cs_endian = match endian {
+        BinaryEndian::Little => capstone::Endian::Little,
+        BinaryEndian::Big => capstone::Endian::Big,
+    };
+
+    let cs = match arch {
+        BinaryArch::AMD64 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .syntax(syntax.into())
+            .detail(true)
+            .build(),
+
+        BinaryArch::X86 => Capstone::new()
+            .x86()
+            .mode(if bits == BinaryBits::Bits32 {
+                arch::x86::ArchMode::Mode32
+            } else {
+                arch::x86::ArchMode::Mode64
+            })
+            .syntax(syntax.into())
+            .detail(true)
+            .build(),
+
+        BinaryArch::ARM => Capstone::new()
+            .arm()
+            .mode(arch::arm::ArchMode::Arm)
+            .endian(cs_endian)
+            .detail(true)
+            .build(),
+
+        BinaryArch::AArch64 => Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .endian(cs_endian)
+            .detail(true)
+            .build(),
+
+        BinaryArch::MIPS => Capstone::new()
+            .mips()
+            .mode(if bits == BinaryBits::Bits32 {
+                arch::mips::ArchMode::Mips32
+            } else {
+                arch::mips::ArchMode::Mips64
+            })
+            .endian(cs_endian)
+            .detail(true)
+            .build(),
+
+        BinaryArch::PowerPC | BinaryArch::PowerPC64 => Capstone::new()
+            .ppc()
+            .mode(if bits == BinaryBits::Bits32 {
+                arch::ppc::ArchMode::Mode32
+            } else {
+                arch::ppc::ArchMode::Mode64
+            })
+            .endian(cs_endian)
+            .detail(true)
+            .build(),
+
+        BinaryArch::RiscV => Capstone::new()
+            .riscv()
+            .mode(if bits == BinaryBits::Bits32 {
+                arch::riscv::ArchMode::RiscV32
+            } else {
+                arch::riscv::ArchMode::RiscV64
+            })
+            .extra_mode(std::iter::once(arch::riscv::ArchExtraMode::RiscVC))
+            .detail(true)
+            .build(),
+
+        _ => return Err(CargoAsmError::UnsupportedArch(format!("{:?}", arch)).into()),
+    };
+
+    Ok(cs.map_err(WCapstoneError)?)
+}
+
+/// Creates a Capstone instance that decodes Thumb instructions. This is used along with the one
+/// returned by `create_capstone` for ARM binaries.
+pub fn create_thumb_capstone(endian: BinaryEndian) -> anyhow::Result<Capstone> {
+    let cs_endian = match endian {
+        BinaryEndian::Little => capstone::Endian::Little,
+        BinaryEndian::Big => capstone::Endian::Big,
+    };
+
+    Ok(Capstone::new()
+        .arm()
+        .mode(arch::arm::ArchMode::Thumb)
+        .endian(cs_endian)
+        .detail(true)
+        .build()
+        .map_err(WCapstoneError)?)
+}
+
+/// Inserts either an inner jump or an operand patch for the instruction at `idx` depending on
+/// whether `target` is inside of the disassembled function or at the start of another symbol.
+fn insert_branch_target<'i, 's>(
+    symbols: &'s [Symbol<'s>],
+    instrs: &[&Insn<'i>],
+    idx: usize,
+    target: u64,
+    jumps: &mut InnerJumpTable,
+    op_patches: &mut OperandPatches<'s>,
+) {
+    // If it's a regular inner jump, then we just display the address and move on.
+    if let Ok(target_index) = instrs.binary_search_by(|rhs| rhs.address().cmp(&target)) {
+        jumps.insert(idx, target_index);
+    } else if let Some(symbol) = symbols.iter().find(|sym| sym.addr == target) {
+        op_patches.insert(idx, symbol);
+    }
+}
+
+pub fn analyze_instructions<'i, 's>(
+    binary: &'s Binary<'s>,
     cs: &Capstone,
-    instrs: &[Insn<'i>],
+    instrs: &[&Insn<'i>],
     jumps: &mut InnerJumpTable,
     op_patches: &mut OperandPatches<'s>,
 ) -> anyhow::Result<()> {
-    #[allow(clippy::single_match)]
-    match arch {
-        BinaryArch::AMD64 => analyze_instructions_amd64(symbols, cs, instrs, jumps, op_patches)?,
+    match binary.arch {
+        BinaryArch::AMD64 | BinaryArch::X86 => {
+            analyze_instructions_amd64(binary, cs, instrs, jumps, op_patches)?
+        }
+        BinaryArch::ARM => {
+            analyze_instructions_arm(&binary.symbols, cs, instrs, jumps, op_patches)?
+        }
+        BinaryArch::AArch64 => {
+            analyze_instructions_aarch64(&binary.symbols, cs, instrs, jumps, op_patches)?
+        }
+        BinaryArch::MIPS => {
+            analyze_instructions_mips(&binary.symbols, cs, instrs, jumps, op_patches)?
+        }
+        BinaryArch::PowerPC | BinaryArch::PowerPC64 => {
+            analyze_instructions_ppc(binary, cs, instrs, jumps, op_patches)?
+        }
+        BinaryArch::RiscV => {
+            analyze_instructions_riscv(&binary.symbols, binary.bits, instrs, jumps, op_patches)
+        }
         _ => { /* NOP */ }
     }
 
+    // Branch targets in a relocatable file are filled in by the linker, so until then they just
+    // point at whatever the unrelocated bytes encode (usually the next instruction).
+    if !binary.relocations.is_empty() {
+        jumps.retain(|jump| {
+            let instr = &instrs[jump.source];
+            let instr_end = instr.address() + instr.bytes().len() as u64;
+            binary.relocations_in(instr.address()..instr_end).is_empty()
+        });
+    }
+
     jumps.sort_and_calc_overlaps();
 
     Ok(())
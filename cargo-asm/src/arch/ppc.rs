@@ -0,0 +1,201 @@
+use super::{insert_branch_target, InnerJumpTable, OperandPatches};
+use crate::binary::{Binary, BinaryBits, BinaryEndian};
+use crate::errors::WCapstoneError;
+use byteorder::{BigEndian, ByteOrder as _, LittleEndian};
+use capstone::arch::ppc::{PpcInsn, PpcOperand, PpcReg};
+use capstone::prelude::*;
+use capstone::Insn;
+
+/// The values of the general purpose registers that are known while walking through a function.
+/// This is only used to follow TOC relative loads of function addresses, e.g.
+///
+/// ```text
+/// addis   r12, r2, -2
+/// ld      r12, 32712(r12)
+/// mtctr   r12
+/// bctrl
+/// ```
+struct PpcRegisters {
+    gpr: [Option<u64>; 32],
+    ctr: Option<u64>,
+}
+
+impl PpcRegisters {
+    fn get(&self, reg: RegId) -> Option<u64> {
+        self.gpr[ppc_gpr_index(reg)?]
+    }
+
+    /// Returns the value of a register that is used as a base address, where r0 reads as zero.
+    fn base(&self, reg: RegId) -> Option<u64> {
+        if reg == RegId(PpcReg::PPC_REG_R0 as _) {
+            Some(0)
+        } else {
+            self.get(reg)
+        }
+    }
+
+    /// Forgets the registers that a called function is allowed to change.
+    fn clobber_volatile(&mut self) {
+        self.gpr[0] = None;
+        for reg in &mut self.gpr[3..=12] {
+            *reg = None;
+        }
+        self.ctr = None;
+    }
+
+    fn set(&mut self, reg: RegId, value: Option<u64>) {
+        if let Some(index) = ppc_gpr_index(reg) {
+            self.gpr[index] = value;
+        }
+    }
+}
+
+pub fn analyze_instructions_ppc<'i, 's>(
+    binary: &'s Binary<'s>,
+    cs: &Capstone,
+    instrs: &[&Insn<'i>],
+    jumps: &mut InnerJumpTable,
+    op_patches: &mut OperandPatches<'s>,
+) -> anyhow::Result<()> {
+    use capstone::arch::ppc::PpcInsnGroup;
+
+    let mut regs = PpcRegisters {
+        gpr: [None; 32],
+        ctr: None,
+    };
+
+    // The ELFv2 ABI passes the address of the global entry point in r12 so that the function can
+    // compute its TOC pointer (r2) from it.
+    if let Some(first) = instrs.first() {
+        regs.gpr[12] = Some(first.address());
+    }
+
+    for (idx, instr) in instrs.iter().enumerate() {
+        let detail = cs.insn_detail(instr).map_err(WCapstoneError)?;
+        let ppc_detail = match detail.arch_detail() {
+            capstone::arch::ArchDetail::PpcDetail(d) => d,
+            _ => continue,
+        };
+        let operands: Vec<PpcOperand> = ppc_detail.operands().collect();
+
+        let is_branch = detail
+            .groups()
+            .iter()
+            .any(|&g| g == InsnGroupId(PpcInsnGroup::PPC_GRP_JUMP as u8));
+
+        if is_branch {
+            let target = match (PpcInsn::from(instr.id().0), operands.last()) {
+                (PpcInsn::PPC_INS_BCTR, _) | (PpcInsn::PPC_INS_BCTRL, _) => regs.ctr,
+                (_, Some(PpcOperand::Imm(target))) => Some(*target as u64),
+                _ => None,
+            };
+
+            if let Some(target) = target {
+                let target = ppc64_global_entry(binary, instrs, target);
+                insert_branch_target(&binary.symbols, instrs, idx, target, jumps, op_patches);
+            }
+
+            if matches!(
+                PpcInsn::from(instr.id().0),
+                PpcInsn::PPC_INS_BL | PpcInsn::PPC_INS_BLA | PpcInsn::PPC_INS_BCTRL
+            ) {
+                regs.clobber_volatile();
+                continue;
+            }
+        }
+
+        ppc_update_registers(binary, &mut regs, instr, &operands);
+    }
+
+    Ok(())
+}
+
+/// Calls within the same module branch to the local entry point of a PPC64 ELFv2 function which
+/// is a few instructions after the symbol's address. This returns the global entry point for
+/// those targets so that they can be matched with a symbol.
+fn ppc64_global_entry(binary: &Binary<'_>, instrs: &[&Insn<'_>], target: u64) -> u64 {
+    // Recursive calls are drawn as inner jumps to the local entry point instead.
+    if instrs
+        .binary_search_by(|rhs| rhs.address().cmp(&target))
+        .is_ok()
+    {
+        return target;
+    }
+
+    binary
+        .local_entries
+        .binary_search_by_key(&target, |e| e.0)
+        .map(|idx| binary.local_entries[idx].1)
+        .unwrap_or(target)
+}
+
+fn ppc_update_registers(
+    binary: &Binary<'_>,
+    regs: &mut PpcRegisters,
+    instr: &Insn<'_>,
+    operands: &[PpcOperand],
+) {
+    use PpcInsn::*;
+
+    let id = PpcInsn::from(instr.id().0);
+
+    let dest = match operands.first() {
+        Some(PpcOperand::Reg(reg)) => *reg,
+        _ => return,
+    };
+
+    let value = match (id, operands.get(1), operands.get(2)) {
+        (PPC_INS_ADDIS, Some(PpcOperand::Reg(base)), Some(PpcOperand::Imm(imm))) => regs
+            .base(*base)
+            .map(|base| base.wrapping_add(((*imm as i16 as i64) << 16) as u64)),
+
+        (PPC_INS_ADDI, Some(PpcOperand::Reg(base)), Some(PpcOperand::Imm(imm))) => regs
+            .base(*base)
+            .map(|base| base.wrapping_add(*imm as i16 as i64 as u64)),
+
+        (PPC_INS_LD, Some(PpcOperand::Mem(mem)), _) if binary.bits == BinaryBits::Bits64 => regs
+            .base(mem.base())
+            .map(|base| base.wrapping_add(mem.disp() as i64 as u64))
+            .and_then(|addr| binary.data_at_addr(addr, 8))
+            .map(|data| match binary.endian {
+                BinaryEndian::Little => LittleEndian::read_u64(data),
+                BinaryEndian::Big => BigEndian::read_u64(data),
+            }),
+
+        (PPC_INS_MTCTR, _, _) => {
+            regs.ctr = regs.get(dest);
+            return;
+        }
+
+        // These only read from their first operand.
+        _ if instr
+            .mnemonic()
+            .map(|m| m.starts_with("st") || m.starts_with("cmp") || m.starts_with("mt"))
+            .unwrap_or(true) =>
+        {
+            return
+        }
+
+        _ => None,
+    };
+
+    // The TOC pointer is the same throughout the function, so it is left alone when it is
+    // restored after a call (`ld r2, 24(r1)`).
+    if dest == RegId(PpcReg::PPC_REG_R2 as _) && value.is_none() {
+        return;
+    }
+
+    regs.set(dest, value);
+}
+
+fn ppc_gpr_index(reg: RegId) -> Option<usize> {
+    let r0 = PpcReg::PPC_REG_R0 as usize;
+    let r31 = PpcReg::PPC_REG_R31 as usize;
+    let reg = reg.0 as usize;
+
+    if (r0..=r31).contains(&reg) {
+        Some(reg - r0)
+    } else {
+        None
+    }
+}
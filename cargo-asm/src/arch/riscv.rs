@@ -0,0 +1,245 @@
+use super::{insert_branch_target, InnerJumpTable, OperandPatches};
+use crate::binary::{BinaryBits, Symbol};
+use byteorder::{ByteOrder, LittleEndian};
+use capstone::Insn;
+
+/// The targets of RISC-V branches are decoded from the instruction bytes instead of Capstone's
+/// operands, which only contain the offset from the branch. Calls and jumps that are too far for
+/// a single instruction are done with an `auipc` followed by a `jalr`, so those pairs are folded
+/// into a single target that is displayed on the `jalr`. `auipc`+`addi` pairs that compute the
+/// address of a symbol are displayed the same way.
+pub fn analyze_instructions_riscv<'i, 's>(
+    symbols: &'s [Symbol<'s>],
+    bits: BinaryBits,
+    instrs: &[&Insn<'i>],
+    jumps: &mut InnerJumpTable,
+    op_patches: &mut OperandPatches<'s>,
+) {
+    let is_64 = bits == BinaryBits::Bits64;
+    let addr_mask = if is_64 { u64::MAX } else { 0xFFFF_FFFF };
+
+    for (idx, instr) in instrs.iter().enumerate() {
+        if let Some(target) = riscv_branch_target(instr.bytes(), instr.address(), is_64) {
+            let target = target & addr_mask;
+            insert_branch_target(symbols, instrs, idx, target, jumps, op_patches);
+            continue;
+        }
+
+        let next = if let Some(next) = instrs.get(idx + 1) {
+            next
+        } else {
+            continue;
+        };
+
+        match riscv_auipc_pair(instr.bytes(), instr.address(), next.bytes()) {
+            Some(AuipcPair::Jump(target)) => {
+                let target = target & addr_mask;
+                insert_branch_target(symbols, instrs, idx + 1, target, jumps, op_patches);
+            }
+            Some(AuipcPair::Address(target)) => {
+                let target = target & addr_mask;
+                if let Some(symbol) = symbols.iter().find(|sym| sym.addr == target) {
+                    op_patches.insert(idx + 1, symbol);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AuipcPair {
+    /// `auipc` followed by `jalr`.
+    Jump(u64),
+
+    /// `auipc` followed by `addi`.
+    Address(u64),
+}
+
+/// Returns the 32-bit instruction word in `bytes`, or `None` for compressed instructions.
+fn riscv_word(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() == 4 && bytes[0] & 0x3 == 0x3 {
+        Some(LittleEndian::read_u32(bytes))
+    } else {
+        None
+    }
+}
+
+/// Sign extends the lowest `bits` bits of `value`.
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let shift = 32 - bits;
+    ((value << shift) as i32 >> shift) as i64
+}
+
+/// Returns the target of a `jal`, a conditional branch or one of their compressed forms. `c.jal`
+/// only exists in RV32 and its encoding is used by `c.addiw` in RV64.
+fn riscv_branch_target(bytes: &[u8], addr: u64, is_64: bool) -> Option<u64> {
+    let offset = match bytes.len() {
+        4 => {
+            let word = riscv_word(bytes)?;
+            match word & 0x7F {
+                // jal rd, offset
+                0x6F => sign_extend(
+                    ((word >> 31) & 0x1) << 20
+                        | ((word >> 21) & 0x3FF) << 1
+                        | ((word >> 20) & 0x1) << 11
+                        | ((word >> 12) & 0xFF) << 12,
+                    21,
+                ),
+                // beq/bne/blt/bge/bltu/bgeu rs1, rs2, offset
+                0x63 => sign_extend(
+                    ((word >> 31) & 0x1) << 12
+                        | ((word >> 25) & 0x3F) << 5
+                        | ((word >> 8) & 0xF) << 1
+                        | ((word >> 7) & 0x1) << 11,
+                    13,
+                ),
+                _ => return None,
+            }
+        }
+
+        2 => {
+            let half = LittleEndian::read_u16(bytes) as u32;
+            match (half & 0x3, half >> 13) {
+                // c.j offset, c.jal offset
+                (0x1, funct3) if funct3 == 0x5 || (funct3 == 0x1 && !is_64) => sign_extend(
+                    ((half >> 12) & 0x1) << 11
+                        | ((half >> 11) & 0x1) << 4
+                        | ((half >> 9) & 0x3) << 8
+                        | ((half >> 8) & 0x1) << 10
+                        | ((half >> 7) & 0x1) << 6
+                        | ((half >> 6) & 0x1) << 7
+                        | ((half >> 3) & 0x7) << 1
+                        | ((half >> 2) & 0x1) << 5,
+                    12,
+                ),
+                // c.beqz rs1', offset, c.bnez rs1', offset
+                (0x1, 0x6) | (0x1, 0x7) => sign_extend(
+                    ((half >> 12) & 0x1) << 8
+                        | ((half >> 10) & 0x3) << 3
+                        | ((half >> 5) & 0x3) << 6
+                        | ((half >> 3) & 0x3) << 1
+                        | ((half >> 2) & 0x1) << 5,
+                    9,
+                ),
+                _ => return None,
+            }
+        }
+
+        _ => return None,
+    };
+
+    Some(addr.wrapping_add(offset as u64))
+}
+
+/// Decodes an `auipc rd, imm` at `addr` followed by a `jalr` or an `addi` that adds to `rd`, and
+/// returns the address that the pair computes.
+fn riscv_auipc_pair(first: &[u8], addr: u64, second: &[u8]) -> Option<AuipcPair> {
+    let auipc = riscv_word(first)?;
+    let rd = (auipc >> 7) & 0x1F;
+    if auipc & 0x7F != 0x17 || rd == 0 {
+        return None;
+    }
+
+    let next = riscv_word(second)?;
+    let funct3 = (next >> 12) & 0x7;
+    let rs1 = (next >> 15) & 0x1F;
+    if funct3 != 0 || rs1 != rd {
+        return None;
+    }
+
+    let hi = (auipc & 0xFFFF_F000) as i32 as i64;
+    let lo = (next as i32 >> 20) as i64;
+    let target = addr.wrapping_add((hi + lo) as u64);
+
+    match next & 0x7F {
+        0x67 => Some(AuipcPair::Jump(target)),
+        0x13 => Some(AuipcPair::Address(target)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{riscv_auipc_pair, riscv_branch_target, AuipcPair};
+
+    #[test]
+    fn riscv_branch_targets() {
+        // jal ra, 0x10
+        let jal = [0xEF, 0x00, 0x00, 0x01];
+        assert_eq!(riscv_branch_target(&jal, 0x1000, true), Some(0x1010));
+
+        // j -8
+        let j = [0x6F, 0xF0, 0x9F, 0xFF];
+        assert_eq!(riscv_branch_target(&j, 0x1008, true), Some(0x1000));
+
+        // beq a0, a1, 12
+        let beq = [0x63, 0x06, 0xB5, 0x00];
+        assert_eq!(riscv_branch_target(&beq, 0x1000, true), Some(0x100C));
+
+        // addi a1, a1, 4
+        let addi = [0x93, 0x85, 0x45, 0x00];
+        assert_eq!(riscv_branch_target(&addi, 0x1000, true), None);
+    }
+
+    #[test]
+    fn riscv_compressed_branch_targets() {
+        // c.j 4
+        assert_eq!(
+            riscv_branch_target(&[0x11, 0xA0], 0x1000, true),
+            Some(0x1004)
+        );
+
+        // c.bnez a0, -4
+        assert_eq!(
+            riscv_branch_target(&[0x75, 0xFD], 0x1004, true),
+            Some(0x1000)
+        );
+
+        // c.jal 4 (RV32 only)
+        assert_eq!(
+            riscv_branch_target(&[0x11, 0x20], 0x1000, false),
+            Some(0x1004)
+        );
+
+        // c.addiw a0, 4 (RV64 only)
+        assert_eq!(riscv_branch_target(&[0x11, 0x25], 0x1000, true), None);
+
+        // c.nop
+        assert_eq!(riscv_branch_target(&[0x01, 0x00], 0x1000, true), None);
+    }
+
+    #[test]
+    fn riscv_auipc_pairs() {
+        // auipc ra, 0x1; jalr ra, 16(ra)
+        let auipc = [0x97, 0x10, 0x00, 0x00];
+        let jalr = [0xE7, 0x80, 0x00, 0x01];
+        assert_eq!(
+            riscv_auipc_pair(&auipc, 0x1000, &jalr),
+            Some(AuipcPair::Jump(0x2010))
+        );
+
+        // auipc t1, 0xfffff; jr t1
+        let auipc = [0x17, 0xF3, 0xFF, 0xFF];
+        let jr = [0x67, 0x00, 0x03, 0x00];
+        assert_eq!(
+            riscv_auipc_pair(&auipc, 0x3000, &jr),
+            Some(AuipcPair::Jump(0x2000))
+        );
+
+        // auipc a0, 0x1; addi a0, a0, -4
+        let auipc = [0x17, 0x15, 0x00, 0x00];
+        let addi = [0x13, 0x05, 0xC5, 0xFF];
+        assert_eq!(
+            riscv_auipc_pair(&auipc, 0x1000, &addi),
+            Some(AuipcPair::Address(0x1FFC))
+        );
+
+        // auipc a0, 0x1; addi a1, a1, 4
+        let addi = [0x93, 0x85, 0x45, 0x00];
+        assert_eq!(riscv_auipc_pair(&auipc, 0x1000, &addi), None);
+
+        // auipc a0, 0x1; c.nop
+        assert_eq!(riscv_auipc_pair(&auipc, 0x1000, &[0x01, 0x00]), None);
+    }
+}
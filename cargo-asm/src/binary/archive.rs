@@ -0,0 +1,165 @@
+use super::{elf, mach, pe, Binary, BinaryData, ObjectExt, Relocation, Symbol};
+use crate::errors::CargoAsmError;
+use anyhow::Context as _;
+use goblin::archive::Archive;
+use goblin::mach::Mach;
+use goblin::Object;
+use std::path::Path;
+
+/// The members of an archive are laid out one after the other in a single address space, each
+/// one starting on a boundary of this many bytes.
+const MEMBER_ALIGNMENT: u64 = 0x1000;
+
+/// An object file inside of a static library (`.a`, `.lib` or `.rlib`).
+#[derive(Debug)]
+pub struct ArchiveMember<'a> {
+    pub name: String,
+
+    /// Offset of the member's contents in the archive.
+    pub offset: usize,
+
+    /// Addresses in the archive that belong to this member. The member's own addresses start at
+    /// `addrs.start` instead of 0.
+    pub addrs: std::ops::Range<u64>,
+
+    pub binary: Binary<'a>,
+}
+
+pub fn analyze_archive<'a>(
+    archive: Archive<'a>,
+    data: &'a BinaryData,
+    binary_path: &Path,
+    load_debug_info: bool,
+) -> anyhow::Result<Binary<'a>> {
+    let mut members: Vec<ArchiveMember<'a>> = Vec::new();
+    let mut next_base_addr = 0;
+
+    for (_, member, _) in archive.summarize() {
+        let name = member.extended_name().to_string();
+        let offset = member.offset as usize;
+        let member_bytes =
+            if let Some(member_bytes) = data.data().get(offset..(offset + member.size())) {
+                member_bytes
+            } else {
+                continue;
+            };
+
+        // Archives also contain symbol tables, and rlibs contain crate metadata, so members that
+        // aren't objects are just skipped.
+        let is_object = match Object::parse(member_bytes) {
+            Ok(Object::Elf(_)) | Ok(Object::Mach(Mach::Binary(_))) => true,
+            _ => pe::parse_coff_object(member_bytes).is_some(),
+        };
+        if !is_object {
+            continue;
+        }
+
+        // File offsets inside of a member are relative to the start of the member, so each one
+        // is analyzed with its own copy of the member's data.
+        let member_data = data.store_member(member_bytes);
+        let binary = match Object::parse(member_data.data()) {
+            Ok(Object::Elf(elf)) => {
+                elf::analyze_elf(elf, member_data, binary_path, None, load_debug_info)
+            }
+            Ok(Object::Mach(Mach::Binary(mach))) => {
+                mach::analyze_mach_object(mach, member_data, binary_path, None, load_debug_info)
+            }
+            _ => match pe::parse_coff_object(member_data.data()) {
+                Some(coff) => pe::analyze_coff(coff, member_data),
+                None => continue,
+            },
+        }
+        .with_context(|| format!("failed to analyze archive member `{}`", name))?;
+
+        let member_end = binary
+            .symbols
+            .iter()
+            .map(|sym| sym.addr + sym.size as u64)
+            .chain(binary.relocations.iter().map(|reloc| reloc.addr + 1))
+            .max()
+            .unwrap_or(0);
+
+        members.push(ArchiveMember {
+            name,
+            offset,
+            addrs: next_base_addr..(next_base_addr + member_end),
+            binary,
+        });
+
+        next_base_addr =
+            (next_base_addr + member_end + MEMBER_ALIGNMENT - 1) & !(MEMBER_ALIGNMENT - 1);
+    }
+
+    let first = if let Some(first) = members.first() {
+        &first.binary
+    } else {
+        return Err(CargoAsmError::UnsupportedBinaryFormat("Archive without object files").into());
+    };
+    let (arch, bits, endian) = (first.arch, first.bits, first.endian);
+
+    let mut symbols = Vec::new();
+    let mut mappings = Vec::new();
+    let mut local_entries = Vec::new();
+    let mut relocations = Vec::new();
+
+    for member in members.iter() {
+        let base_addr = member.addrs.start;
+
+        for sym in member.binary.symbols.iter() {
+            symbols.push(Symbol {
+                original_name: sym.original_name.clone(),
+                demangled_name: sym.demangled_name.clone(),
+                short_demangled_name: Default::default(),
+
+                addr: base_addr + sym.addr,
+                offset: member.offset + sym.offset,
+                size: sym.size,
+                size_inferred: sym.size_inferred,
+                archive_member: Some(member.name.clone()),
+            });
+        }
+
+        mappings.extend(
+            member
+                .binary
+                .mappings
+                .iter()
+                .map(|&(addr, kind)| (base_addr + addr, kind)),
+        );
+
+        local_entries.extend(
+            member
+                .binary
+                .local_entries
+                .iter()
+                .map(|&(local, global)| (base_addr + local, base_addr + global)),
+        );
+
+        relocations.extend(member.binary.relocations.iter().map(|reloc| Relocation {
+            addr: base_addr + reloc.addr,
+            kind: reloc.kind.clone(),
+            pc_relative: reloc.pc_relative,
+            symbol: reloc.symbol.clone(),
+            addend: reloc.addend,
+        }));
+    }
+
+    Ok(Binary {
+        data,
+        bits,
+        arch,
+        endian,
+        symbols,
+        mappings,
+        local_entries,
+        relocations,
+        import_symbols: Vec::new(),
+        object: ObjectExt::Archive(members),
+    })
+}
+
+pub(super) fn archive_addr_to_offset(members: &[ArchiveMember], addr: u64) -> Option<usize> {
+    let member = members.iter().find(|member| member.addrs.contains(&addr))?;
+    let member_offset = member.binary.addr_to_offset(addr - member.addrs.start)?;
+    Some(member.offset + member_offset)
+}
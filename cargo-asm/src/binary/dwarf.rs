@@ -1,4 +1,5 @@
-use super::{FileResolveStrategy, LineMapper};
+use super::split_dwarf::{SplitDwarf, SplitReader};
+use super::{source_path_matches, FileResolveStrategy, LineMapper};
 use crate::platform::PathConverter;
 use once_cell::unsync::OnceCell;
 use std::ops::Range;
@@ -15,6 +16,9 @@ pub struct DwarfLineMapper<R: gimli::Reader> {
 
     base_directory: PathBuf,
     resolve_strategy: FileResolveStrategy,
+
+    /// Used to find the split units of skeleton compilation units.
+    split_dwarf: Option<SplitDwarf>,
 }
 
 impl<R: gimli::Reader> DwarfLineMapper<R> {
@@ -28,11 +32,15 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
         L: Fn(gimli::SectionId) -> Result<R, anyhow::Error>,
         S: Fn(gimli::SectionId) -> Result<R, anyhow::Error>,
     {
-        let dwarf = gimli::Dwarf::load(loader, sup_loader)?;
+        let dwarf = gimli::Dwarf::load(&loader, &sup_loader)?;
+
+        // The supplementary file's own DWARF, which contains the partial units that are imported
+        // with `DW_FORM_GNU_ref_alt`. It's empty if there isn't a supplementary file.
+        let sup_dwarf = gimli::Dwarf::load(&sup_loader, &sup_loader)?;
 
         // FIXME make loading compilation units lazy so that there is no penalty
         //       if DWARF life mappings are not requested.
-        let (units, unit_ranges) = Self::find_compilation_units(&dwarf)?;
+        let (units, unit_ranges) = Self::find_compilation_units(&dwarf, &sup_dwarf)?;
 
         Ok(DwarfLineMapper {
             dwarf,
@@ -40,9 +48,23 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
             units,
             base_directory: PathBuf::from(base_directory),
             resolve_strategy,
+            split_dwarf: None,
         })
     }
 
+    pub fn with_split_dwarf(mut self, split_dwarf: SplitDwarf) -> Self {
+        self.split_dwarf = Some(split_dwarf);
+        self
+    }
+
+    /// Used for the DWARF of relocatable files, where code can be at address 0.
+    pub fn with_relocatable_addresses(mut self) -> Self {
+        for unit in self.units.iter_mut() {
+            unit.allow_zero_address = true;
+        }
+        self
+    }
+
     fn unit_index_for_address(&self, seek_addr: u64) -> Option<usize> {
         self.unit_ranges
             .binary_search_by(|probe| {
@@ -60,6 +82,7 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
 
     fn find_compilation_units(
         dwarf: &gimli::Dwarf<R>,
+        sup_dwarf: &gimli::Dwarf<R>,
     ) -> anyhow::Result<(Vec<LazyUnit<R>>, Vec<UnitRange>)> {
         let mut lazy_units = Vec::new();
         let mut unit_ranges = Vec::new();
@@ -72,7 +95,7 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
                 continue;
             };
 
-            Self::add_compilation_unit(dwarf, unit, &mut lazy_units, &mut unit_ranges)?;
+            Self::add_compilation_unit(dwarf, sup_dwarf, unit, &mut lazy_units, &mut unit_ranges)?;
         }
 
         unit_ranges.sort_unstable_by_key(|r| r.0.start);
@@ -82,6 +105,7 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
 
     fn add_compilation_unit(
         dwarf: &gimli::Dwarf<R>,
+        sup_dwarf: &gimli::Dwarf<R>,
         unit: gimli::Unit<R>,
         lazy_units: &mut Vec<LazyUnit<R>>,
         unit_ranges: &mut Vec<UnitRange>,
@@ -89,7 +113,12 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
         let mut entries = unit.entries_raw(None)?;
 
         let abbrev = match entries.read_abbreviation()? {
-            Some(abbrev) if abbrev.tag() == gimli::DW_TAG_compile_unit => abbrev,
+            Some(abbrev)
+                if abbrev.tag() == gimli::DW_TAG_compile_unit
+                    || abbrev.tag() == gimli::DW_TAG_skeleton_unit =>
+            {
+                abbrev
+            }
             _ => return Ok(()),
         };
 
@@ -98,6 +127,8 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
         let mut size = None;
         let mut ranges = None;
         let mut lang = None;
+        let mut dwo_name = None;
+        let mut dwo_id = None;
 
         for spec in abbrev.attributes() {
             let attr = entries.read_attribute(*spec)?;
@@ -127,10 +158,25 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
                     }
                 }
 
+                gimli::DW_AT_GNU_dwo_name | gimli::DW_AT_dwo_name => {
+                    if let Ok(name) = dwarf.attr_string(&unit, attr.value()) {
+                        dwo_name = Some(name.to_string_lossy()?.into_owned());
+                    }
+                }
+
+                gimli::DW_AT_GNU_dwo_id => {
+                    dwo_id = attr.udata_value();
+                }
+
                 _ => { /* NOP */ }
             }
         }
 
+        // DWARF 5 skeleton units have the ID of their split unit in the unit header.
+        if let gimli::UnitType::Skeleton(id) = unit.header.type_() {
+            dwo_id = Some(id.0);
+        }
+
         let unit_index = lazy_units.len();
         if let Some(offset) = ranges {
             let mut ranges = dwarf.ranges(&unit, offset)?;
@@ -141,12 +187,99 @@ impl<R: gimli::Reader> DwarfLineMapper<R> {
             unit_ranges.push((begin..end, unit_index));
         } else if let (Some(begin), Some(size)) = (start_addr, size) {
             unit_ranges.push((begin..(begin + size), unit_index));
+        } else {
+            let mut imported = Vec::new();
+            Self::add_function_ranges(
+                dwarf,
+                sup_dwarf,
+                &unit,
+                unit_index,
+                unit_ranges,
+                &mut imported,
+            )?;
         }
 
-        lazy_units.push(LazyUnit::new(unit, lang));
+        let split = match (dwo_name, dwo_id) {
+            (Some(dwo_name), Some(dwo_id)) => Some(SplitUnit { dwo_name, dwo_id }),
+            _ => None,
+        };
+
+        lazy_units.push(LazyUnit::new(unit, lang, split));
 
         Ok(())
     }
+
+    /// Adds the address ranges of the functions in a unit for compilation units that don't have
+    /// any address ranges themselves. Functions in partial units that are imported into the unit
+    /// count too, which are referenced with `DW_FORM_ref_addr`, or with `DW_FORM_GNU_ref_alt` if
+    /// they're in the supplementary file. `imported` holds the units that were already visited.
+    fn add_function_ranges(
+        dwarf: &gimli::Dwarf<R>,
+        sup_dwarf: &gimli::Dwarf<R>,
+        unit: &gimli::Unit<R>,
+        unit_index: usize,
+        unit_ranges: &mut Vec<UnitRange>,
+        imported: &mut Vec<(bool, R::Offset)>,
+    ) -> anyhow::Result<()> {
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            match entry.tag() {
+                gimli::DW_TAG_subprogram => {
+                    let mut ranges = dwarf.die_ranges(unit, entry)?;
+                    while let Some(range) = ranges.next()? {
+                        if range.begin < range.end {
+                            unit_ranges.push((range.begin..range.end, unit_index));
+                        }
+                    }
+                }
+
+                gimli::DW_TAG_imported_unit => {
+                    let (import_dwarf, offset, is_sup) = match entry
+                        .attr_value(gimli::DW_AT_import)?
+                    {
+                        Some(gimli::AttributeValue::DebugInfoRef(offset)) => (dwarf, offset, false),
+                        Some(gimli::AttributeValue::DebugInfoRefSup(offset)) => {
+                            (sup_dwarf, offset, true)
+                        }
+                        _ => continue,
+                    };
+
+                    if imported.contains(&(is_sup, offset.0)) {
+                        continue;
+                    }
+                    imported.push((is_sup, offset.0));
+
+                    if let Some(imported_unit) = Self::unit_containing(import_dwarf, offset)? {
+                        Self::add_function_ranges(
+                            import_dwarf,
+                            sup_dwarf,
+                            &imported_unit,
+                            unit_index,
+                            unit_ranges,
+                            imported,
+                        )?;
+                    }
+                }
+
+                _ => { /* NOP */ }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn unit_containing(
+        dwarf: &gimli::Dwarf<R>,
+        offset: gimli::DebugInfoOffset<R::Offset>,
+    ) -> anyhow::Result<Option<gimli::Unit<R>>> {
+        let mut unit_headers = dwarf.units();
+        while let Some(unit_header) = unit_headers.next()? {
+            if offset.to_unit_offset(&unit_header).is_some() {
+                return Ok(Some(dwarf.unit(unit_header)?));
+            }
+        }
+        Ok(None)
+    }
 }
 
 impl<R: gimli::Reader> LineMapper for DwarfLineMapper<R> {
@@ -158,6 +291,7 @@ impl<R: gimli::Reader> LineMapper for DwarfLineMapper<R> {
         if let Some(unit_index) = self.unit_index_for_address(address) {
             self.units[unit_index].addr2line(
                 &self.dwarf,
+                self.split_dwarf.as_ref(),
                 address,
                 &self.base_directory,
                 self.resolve_strategy,
@@ -167,6 +301,54 @@ impl<R: gimli::Reader> LineMapper for DwarfLineMapper<R> {
             Ok(None)
         }
     }
+
+    fn map_line_to_addresses(
+        &mut self,
+        file: &Path,
+        line: u32,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Vec<Range<u64>>> {
+        let mut ranges = Vec::new();
+        for unit in self.units.iter() {
+            // Units without usable line information are skipped like in `pdb_lines`. The file
+            // table is checked first so that only the rows of units that contain the file are
+            // loaded.
+            let files = match unit.files(
+                &self.dwarf,
+                self.split_dwarf.as_ref(),
+                &self.base_directory,
+                self.resolve_strategy,
+                convert_path,
+            ) {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+
+            let matching_files = files
+                .iter()
+                .map(|source_file| {
+                    source_file
+                        .as_ref()
+                        .map(|source_file| source_file.matches(file, &self.base_directory))
+                        .unwrap_or(false)
+                })
+                .collect::<Vec<bool>>();
+            if !matching_files.contains(&true) {
+                continue;
+            }
+
+            if let Ok(sequences) = unit.sequences(
+                &self.dwarf,
+                self.split_dwarf.as_ref(),
+                &self.base_directory,
+                self.resolve_strategy,
+                convert_path,
+            ) {
+                ranges_for_line(sequences, &matching_files, line, &mut ranges);
+            }
+        }
+        Ok(ranges)
+    }
 }
 
 pub struct LazyUnit<R: gimli::Reader> {
@@ -176,40 +358,290 @@ pub struct LazyUnit<R: gimli::Reader> {
     #[allow(dead_code)]
     lang: Option<gimli::DwLang>,
 
-    lines: OnceCell<Lines>,
+    /// Set if this is a skeleton unit for a split unit in a `.dwo` or `.dwp` file.
+    split: Option<SplitUnit>,
+
+    /// Code in relocatable files starts at address 0, which otherwise marks the line programs of
+    /// functions that the linker removed.
+    allow_zero_address: bool,
+
+    files: OnceCell<Box<[Option<SourceFile>]>>,
+    sequences: OnceCell<Box<[Sequence]>>,
+}
+
+pub struct SplitUnit {
+    dwo_name: String,
+    dwo_id: u64,
 }
 
 impl<R: gimli::Reader> LazyUnit<R> {
-    pub fn new(unit: gimli::Unit<R>, lang: Option<gimli::DwLang>) -> LazyUnit<R> {
+    pub fn new(
+        unit: gimli::Unit<R>,
+        lang: Option<gimli::DwLang>,
+        split: Option<SplitUnit>,
+    ) -> LazyUnit<R> {
         LazyUnit {
             unit,
             lang,
-            lines: OnceCell::default(),
+            split,
+            allow_zero_address: false,
+            files: OnceCell::default(),
+            sequences: OnceCell::default(),
         }
     }
 
-    fn lines(
+    /// Returns the files of the unit's line program, indexed like in the line program.
+    fn files(
         &self,
         dwarf: &gimli::Dwarf<R>,
+        split_dwarf: Option<&SplitDwarf>,
         base_directory: &Path,
         resolve_strategy: FileResolveStrategy,
         convert_path: &dyn PathConverter,
-    ) -> anyhow::Result<&Lines> {
-        self.lines.get_or_try_init(|| {
-            self.load_lines(dwarf, base_directory, resolve_strategy, convert_path)
+    ) -> anyhow::Result<&[Option<SourceFile>]> {
+        self.files
+            .get_or_try_init(|| {
+                self.load_files(
+                    dwarf,
+                    split_dwarf,
+                    base_directory,
+                    resolve_strategy,
+                    convert_path,
+                )
+            })
+            .map(|files| &**files)
+    }
+
+    /// Returns the sequences of rows of the unit's line program, sorted by address.
+    fn sequences(
+        &self,
+        dwarf: &gimli::Dwarf<R>,
+        split_dwarf: Option<&SplitDwarf>,
+        base_directory: &Path,
+        resolve_strategy: FileResolveStrategy,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<&[Sequence]> {
+        self.sequences
+            .get_or_try_init(|| {
+                self.load_sequences(
+                    dwarf,
+                    split_dwarf,
+                    base_directory,
+                    resolve_strategy,
+                    convert_path,
+                )
+            })
+            .map(|sequences| &**sequences)
+    }
+
+    fn comp_dir(&self) -> anyhow::Result<Option<String>> {
+        match self.unit.comp_dir {
+            Some(ref comp_dir) => Ok(Some(comp_dir.to_string_lossy()?.into_owned())),
+            None => Ok(None),
+        }
+    }
+
+    fn load_files(
+        &self,
+        dwarf: &gimli::Dwarf<R>,
+        split_dwarf: Option<&SplitDwarf>,
+        base_directory: &Path,
+        resolve_strategy: FileResolveStrategy,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Box<[Option<SourceFile>]>> {
+        let comp_dir = self.comp_dir()?;
+
+        // Skeleton units normally keep the line program in the binary, but if one doesn't the
+        // line program of its split unit is used instead.
+        if self.unit.line_program.is_none() {
+            let files = self.with_split_unit(
+                split_dwarf,
+                comp_dir.as_deref(),
+                convert_path,
+                |split_dwarf, split_unit| {
+                    let loader = LinesLoader {
+                        dwarf: split_dwarf,
+                        unit: split_unit,
+                        comp_dir: comp_dir.as_deref(),
+                        base_directory,
+                        resolve_strategy,
+                        convert_path,
+                        allow_zero_address: self.allow_zero_address,
+                    };
+                    loader.load_files(|| loader.unit_file())
+                },
+            );
+            return Ok(files.transpose()?.unwrap_or_default());
+        }
+
+        let loader = LinesLoader {
+            dwarf,
+            unit: &self.unit,
+            comp_dir: comp_dir.as_deref(),
+            base_directory,
+            resolve_strategy,
+            convert_path,
+            allow_zero_address: self.allow_zero_address,
+        };
+
+        // A skeleton unit doesn't have a name so that is taken from its split unit.
+        loader.load_files(|| {
+            self.with_split_unit(
+                split_dwarf,
+                comp_dir.as_deref(),
+                convert_path,
+                |split_dwarf, split_unit| {
+                    LinesLoader {
+                        dwarf: split_dwarf,
+                        unit: split_unit,
+                        comp_dir: comp_dir.as_deref(),
+                        base_directory,
+                        resolve_strategy,
+                        convert_path,
+                        allow_zero_address: self.allow_zero_address,
+                    }
+                    .unit_file()
+                },
+            )
+            .transpose()
+            .map(Option::flatten)
         })
     }
 
-    fn load_lines(
+    fn load_sequences(
+        &self,
+        dwarf: &gimli::Dwarf<R>,
+        split_dwarf: Option<&SplitDwarf>,
+        base_directory: &Path,
+        resolve_strategy: FileResolveStrategy,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Box<[Sequence]>> {
+        let comp_dir = self.comp_dir()?;
+
+        if self.unit.line_program.is_none() {
+            let sequences = self.with_split_unit(
+                split_dwarf,
+                comp_dir.as_deref(),
+                convert_path,
+                |split_dwarf, split_unit| {
+                    LinesLoader {
+                        dwarf: split_dwarf,
+                        unit: split_unit,
+                        comp_dir: comp_dir.as_deref(),
+                        base_directory,
+                        resolve_strategy,
+                        convert_path,
+                        allow_zero_address: self.allow_zero_address,
+                    }
+                    .load_sequences()
+                },
+            );
+            return Ok(sequences.transpose()?.unwrap_or_default());
+        }
+
+        LinesLoader {
+            dwarf,
+            unit: &self.unit,
+            comp_dir: comp_dir.as_deref(),
+            base_directory,
+            resolve_strategy,
+            convert_path,
+            allow_zero_address: self.allow_zero_address,
+        }
+        .load_sequences()
+    }
+
+    /// Calls `f` with the split unit of a skeleton unit.
+    fn with_split_unit<T, F>(
+        &self,
+        split_dwarf: Option<&SplitDwarf>,
+        comp_dir: Option<&str>,
+        convert_path: &dyn PathConverter,
+        f: F,
+    ) -> Option<T>
+    where
+        F: FnOnce(&gimli::Dwarf<SplitReader>, &gimli::Unit<SplitReader>) -> T,
+    {
+        let (split, split_dwarf) = match (&self.split, split_dwarf) {
+            (Some(split), Some(split_dwarf)) => (split, split_dwarf),
+            _ => return None,
+        };
+
+        let comp_dir =
+            comp_dir.map(|comp_dir| PathBuf::from(convert_path.convert(comp_dir).as_ref()));
+        let result = split_dwarf.with_unit(&split.dwo_name, comp_dir.as_deref(), split.dwo_id, f);
+        if result.is_none() {
+            split_dwarf.warn_missing(&split.dwo_name);
+        }
+        result
+    }
+
+    pub fn addr2line(
         &self,
         dwarf: &gimli::Dwarf<R>,
+        split_dwarf: Option<&SplitDwarf>,
+        addr: u64,
         base_directory: &Path,
         resolve_strategy: FileResolveStrategy,
         convert_path: &dyn PathConverter,
-    ) -> anyhow::Result<Lines> {
+    ) -> anyhow::Result<Option<(&Path, u32)>> {
+        let sequences = self.sequences(
+            dwarf,
+            split_dwarf,
+            base_directory,
+            resolve_strategy,
+            convert_path,
+        )?;
+        let line = match line_for_addr(sequences, addr) {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        let files = self.files(
+            dwarf,
+            split_dwarf,
+            base_directory,
+            resolve_strategy,
+            convert_path,
+        )?;
+        Ok(files
+            .get(line.file)
+            .and_then(Option::as_ref)
+            .map(|source_file| (source_file.path(), line.line)))
+    }
+}
+
+/// Loads the line program of a compilation unit, which is either a unit of the binary or a split
+/// unit in a `.dwo` file or `.dwp` package.
+struct LinesLoader<'l, S: gimli::Reader> {
+    dwarf: &'l gimli::Dwarf<S>,
+    unit: &'l gimli::Unit<S>,
+
+    /// The compilation directory of the unit, or of the skeleton unit for split units.
+    comp_dir: Option<&'l str>,
+
+    base_directory: &'l Path,
+    resolve_strategy: FileResolveStrategy,
+    convert_path: &'l dyn PathConverter,
+
+    /// See `LazyUnit::allow_zero_address`.
+    allow_zero_address: bool,
+}
+
+impl<'l, S: gimli::Reader> Clone for LinesLoader<'l, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'l, S: gimli::Reader> Copy for LinesLoader<'l, S> {}
+
+impl<'l, S: gimli::Reader> LinesLoader<'l, S> {
+    /// Returns the sequences of rows of the line program, sorted by address.
+    fn load_sequences(&self) -> anyhow::Result<Box<[Sequence]>> {
         let inc_line_program = match self.unit.line_program {
             Some(ref line_prog) => line_prog,
-            None => return Ok(Lines::empty()),
+            None => return Ok(Box::default()),
         };
 
         let mut sequences = Vec::new();
@@ -223,7 +655,7 @@ impl<R: gimli::Reader> LazyUnit<R> {
             let address = row.address();
 
             if row.end_sequence() {
-                if seq_start_addr != 0 && !lines.is_empty() {
+                if (seq_start_addr != 0 || self.allow_zero_address) && !lines.is_empty() {
                     // FIXME lines should be sorted by address I think but I'm not sure. If not I
                     //       should sort them here.
                     sequences.push(Sequence {
@@ -262,102 +694,92 @@ impl<R: gimli::Reader> LazyUnit<R> {
         }
 
         sequences.sort_by_key(|seq| seq.range.start);
+        Ok(sequences.into_boxed_slice())
+    }
+
+    /// Returns the files of the line program. `unit_file` is used for file 0 if it isn't in the
+    /// line program's file table.
+    fn load_files<F>(&self, unit_file: F) -> anyhow::Result<Box<[Option<SourceFile>]>>
+    where
+        F: FnOnce() -> anyhow::Result<Option<PathBuf>>,
+    {
+        let inc_line_program = match self.unit.line_program {
+            Some(ref line_prog) => line_prog,
+            None => return Ok(Box::default()),
+        };
 
-        let mut files = Vec::new();
+        // Before DWARF 5, file 0 is the compilation unit's own file, which isn't part of the file
+        // table.
         let header = inc_line_program.header();
-        let mut idx = 0;
-        while let Some(file) = header.file(idx) {
-            files.push(self.render_file(
-                file,
-                &header,
-                dwarf,
-                base_directory,
-                resolve_strategy,
-                convert_path,
-            )?);
-            idx += 1;
+        let file_count = header.file_names().len() as u64 + (header.version() <= 4) as u64;
+        let mut files = Vec::with_capacity(file_count as usize);
+        let mut unit_file = Some(unit_file);
+        for idx in 0..file_count {
+            if let Some(file) = header.file(idx) {
+                files.push(Some(self.render_file(file, &header)?));
+            } else if let (0, Some(unit_file)) = (idx, unit_file.take()) {
+                files.push(unit_file()?.map(SourceFile::new));
+            } else {
+                files.push(None);
+            }
         }
 
-        Ok(Lines {
-            sequences: sequences.into_boxed_slice(),
-            files: files.into_boxed_slice(),
-        })
+        Ok(files.into_boxed_slice())
     }
 
-    fn render_file(
-        &self,
-        file: &gimli::FileEntry<R, R::Offset>,
-        header: &gimli::LineProgramHeader<R, R::Offset>,
-        dwarf: &gimli::Dwarf<R>,
-        base_directory: &Path,
-        resolve_strategy: FileResolveStrategy,
-        convert_path: &dyn PathConverter,
-    ) -> anyhow::Result<PathBuf> {
-        let preferred_path = self.subrender_file(
-            file,
-            header,
-            dwarf,
-            base_directory,
-            resolve_strategy,
-            convert_path,
-        )?;
+    /// Returns the path of the file that the unit was compiled from.
+    fn unit_file(&self) -> anyhow::Result<Option<PathBuf>> {
+        let name = match self.unit.name {
+            Some(ref name) => name.to_string_lossy()?,
+            None => return Ok(None),
+        };
 
-        if preferred_path.is_file() {
-            Ok(preferred_path)
-        } else {
-            // FIXME reuse the preferred path when the `clear` is stabilized for PathBuf.
-            self.subrender_file(
-                file,
-                header,
-                dwarf,
-                base_directory,
-                resolve_strategy.other(),
-                convert_path,
-            )
-        }
+        let name = PathBuf::from(self.convert_path.convert(name.as_ref()).as_ref());
+        Ok(Some(match self.comp_dir {
+            Some(comp_dir) => {
+                PathBuf::from(self.convert_path.convert(comp_dir).as_ref()).join(name)
+            }
+            None => name,
+        }))
     }
 
-    pub fn addr2line(
+    fn render_file(
         &self,
-        dwarf: &gimli::Dwarf<R>,
-        addr: u64,
-        base_directory: &Path,
-        resolve_strategy: FileResolveStrategy,
-        convert_path: &dyn PathConverter,
-    ) -> anyhow::Result<Option<(&Path, u32)>> {
-        self.lines(dwarf, base_directory, resolve_strategy, convert_path)
-            .map(|lines| lines.lines_for_addr(addr))
+        file: &gimli::FileEntry<S, S::Offset>,
+        header: &gimli::LineProgramHeader<S, S::Offset>,
+    ) -> anyhow::Result<SourceFile> {
+        Ok(SourceFile {
+            preferred: self.subrender_file(file, header, self.resolve_strategy)?,
+            fallback: Some(self.subrender_file(file, header, self.resolve_strategy.other())?),
+            resolved: OnceCell::new(),
+        })
     }
 
     /// This should only be called by `render_file`
     fn subrender_file(
         &self,
-        file: &gimli::FileEntry<R, R::Offset>,
-        header: &gimli::LineProgramHeader<R, R::Offset>,
-        dwarf: &gimli::Dwarf<R>,
-        base_directory: &Path,
+        file: &gimli::FileEntry<S, S::Offset>,
+        header: &gimli::LineProgramHeader<S, S::Offset>,
         resolve_strategy: FileResolveStrategy,
-        convert_path: &dyn PathConverter,
     ) -> anyhow::Result<PathBuf> {
+        let convert_path = self.convert_path;
         let mut path = if resolve_strategy == FileResolveStrategy::PreferRelative {
-            PathBuf::from(base_directory)
+            PathBuf::from(self.base_directory)
         } else {
             PathBuf::new()
         };
 
-        if let Some(ref comp_dir) = self.unit.comp_dir {
-            let comp_dir = comp_dir.to_string_lossy()?;
-
+        if let Some(comp_dir) = self.comp_dir {
             if resolve_strategy == FileResolveStrategy::PreferAbsolute
-                || convert_path.is_relative(comp_dir.as_ref())
+                || convert_path.is_relative(comp_dir)
             {
-                let comp_dir = convert_path.convert(comp_dir.as_ref());
+                let comp_dir = convert_path.convert(comp_dir);
                 path.push(comp_dir.as_ref());
             }
         }
 
         if let Some(directory) = file.directory(header) {
-            let directory_raw = dwarf.attr_string(&self.unit, directory)?;
+            let directory_raw = self.dwarf.attr_string(self.unit, directory)?;
             let directory = directory_raw.to_string_lossy()?;
 
             if resolve_strategy == FileResolveStrategy::PreferAbsolute
@@ -369,8 +791,8 @@ impl<R: gimli::Reader> LazyUnit<R> {
         }
 
         path.push(
-            dwarf
-                .attr_string(&self.unit, file.path_name())?
+            self.dwarf
+                .attr_string(self.unit, file.path_name())?
                 .to_string_lossy()?
                 .as_ref(),
         );
@@ -379,40 +801,92 @@ impl<R: gimli::Reader> LazyUnit<R> {
     }
 }
 
-struct Lines {
-    sequences: Box<[Sequence]>,
-    files: Box<[PathBuf]>,
+/// A file of a line program. Whether it is opened using the path preferred by the
+/// `FileResolveStrategy` or the other one is only decided when it is first needed, because that
+/// requires checking if the preferred path exists.
+struct SourceFile {
+    preferred: PathBuf,
+
+    /// Used instead of `preferred` if that doesn't exist.
+    fallback: Option<PathBuf>,
+
+    resolved: OnceCell<PathBuf>,
 }
 
-impl Lines {
-    pub fn empty() -> Lines {
-        Lines {
-            sequences: Box::new([] as [Sequence; 0]),
-            files: Box::new([] as [PathBuf; 0]),
+impl SourceFile {
+    fn new(path: PathBuf) -> SourceFile {
+        SourceFile {
+            preferred: path,
+            fallback: None,
+            resolved: OnceCell::new(),
         }
     }
 
-    fn lines_for_addr(&self, addr: u64) -> Option<(&Path, u32)> {
-        let sequence = self
-            .sequences
-            .binary_search_by(|probe| {
-                if probe.range.start > addr {
-                    std::cmp::Ordering::Greater
-                } else if probe.range.end <= addr {
-                    std::cmp::Ordering::Less
-                } else {
-                    std::cmp::Ordering::Equal
-                }
-            })
-            .ok()
-            .and_then(|seq_idx| self.sequences.get(seq_idx))?;
+    fn path(&self) -> &Path {
+        self.resolved.get_or_init(|| match self.fallback {
+            Some(ref fallback) if !self.preferred.is_file() => fallback.clone(),
+            _ => self.preferred.clone(),
+        })
+    }
 
-        sequence
-            .lines
-            .binary_search_by(|probe| probe.addr.cmp(&addr))
-            .ok()
-            .and_then(|line_idx| sequence.lines.get(line_idx))
-            .map(|line| (self.files[line.file].as_path(), line.line))
+    /// Returns true if `file` names this file. The file system is only checked if `file` only
+    /// names one of the two paths.
+    fn matches(&self, file: &Path, base_directory: &Path) -> bool {
+        let preferred_matches = source_path_matches(&self.preferred, file, base_directory);
+        match self.fallback {
+            Some(ref fallback)
+                if source_path_matches(fallback, file, base_directory) != preferred_matches =>
+            {
+                source_path_matches(self.path(), file, base_directory)
+            }
+            _ => preferred_matches,
+        }
+    }
+}
+
+/// Returns the row of `sequences` for the address `addr`.
+fn line_for_addr(sequences: &[Sequence], addr: u64) -> Option<&Line> {
+    let sequence = sequences
+        .binary_search_by(|probe| {
+            if probe.range.start > addr {
+                std::cmp::Ordering::Greater
+            } else if probe.range.end <= addr {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .ok()
+        .and_then(|seq_idx| sequences.get(seq_idx))?;
+
+    sequence
+        .lines
+        .binary_search_by(|probe| probe.addr.cmp(&addr))
+        .ok()
+        .and_then(|line_idx| sequence.lines.get(line_idx))
+}
+
+/// Adds the address ranges of the rows for `line` of the files marked in `matching_files` to
+/// `ranges`. A row covers the addresses up to the next row of its sequence.
+fn ranges_for_line(
+    sequences: &[Sequence],
+    matching_files: &[bool],
+    line: u32,
+    ranges: &mut Vec<Range<u64>>,
+) {
+    for sequence in sequences.iter() {
+        for (idx, row) in sequence.lines.iter().enumerate() {
+            if row.line != line || !matching_files.get(row.file).copied().unwrap_or(false) {
+                continue;
+            }
+
+            let end = sequence
+                .lines
+                .get(idx + 1)
+                .map(|next| next.addr)
+                .unwrap_or(sequence.range.end);
+            ranges.push(row.addr..end);
+        }
     }
 }
 
@@ -1,18 +1,25 @@
 use super::dwarf::DwarfLineMapper;
+use super::split_dwarf::SplitDwarf;
 use super::{
-    demangle_name, Binary, BinaryArch, BinaryBits, BinaryData, BinaryEndian, FileResolveStrategy,
-    LineMapper, ObjectExt, Symbol,
+    demangle_name, infer_symbol_sizes, Binary, BinaryArch, BinaryBits, BinaryData, BinaryEndian,
+    FileResolveStrategy, LineMapper, MappingKind, ObjectExt, Relocation, Symbol,
 };
+use anyhow::Context as _;
+use byteorder::{ByteOrder as _, LittleEndian};
 use goblin::elf::Elf;
 use std::borrow::Cow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub fn analyze_elf<'a>(
-    elf: Elf<'a>,
+    mut elf: Elf<'a>,
     data: &'a BinaryData,
+    binary_path: &Path,
+    debug_file: Option<&Path>,
     _load_debug_info: bool,
 ) -> anyhow::Result<Binary<'a>> {
     use goblin::elf::header;
+    use goblin::elf::section_header::SHN_UNDEF;
+    use goblin::elf::sym::{STB_GLOBAL, STB_WEAK, STT_NOTYPE};
 
     let bits = BinaryBits::from_elf_class(elf.header.e_ident[header::EI_CLASS])
         .expect("[FIXME] unrecognized bits value");
@@ -22,15 +29,54 @@ pub fn analyze_elf<'a>(
     let arch =
         BinaryArch::from_elf_machine(elf.header.e_machine).expect("[FIXME] unrecognized arch");
 
+    let is_relocatable = elf.header.e_type == header::ET_REL;
+    if is_relocatable {
+        layout_relocatable_sections(&mut elf);
+    }
+
     let mut symbols = Vec::new();
+    let mut mappings = Vec::new();
+    let mut local_entries = Vec::new();
+    let mut relocations = Vec::new();
 
-    for sym in elf.syms.iter().filter(|sym| sym.is_function()) {
-        // FIXME handle these symbols with a size of 0 (external symbols usually).
-        if sym.st_size == 0 {
+    if arch == BinaryArch::ARM {
+        get_arm_mapping_symbols(&elf, &mut mappings)?;
+    }
+
+    // Stripped binaries only have the dynamic symbol table left, which contains the exported
+    // functions. The symbol table always starts with a null symbol, so it's also considered
+    // missing if that's the only entry. Relocatable objects have no dynamic symbols to fall back to.
+    let is_stripped = !is_relocatable && elf.syms.len() <= 1;
+    let (syms, strtab) = if is_stripped {
+        (&elf.dynsyms, &elf.dynstrtab)
+    } else {
+        (&elf.syms, &elf.strtab)
+    };
+
+    // Indices of the symbols that have no size along with the end address of their sections.
+    let mut unsized_symbols = Vec::new();
+
+    for sym in syms.iter() {
+        if sym.st_shndx == SHN_UNDEF as usize {
             continue;
         }
 
-        let sym_name = if let Some(name) = elf.strtab.get(sym.st_name).transpose()? {
+        let sym_section = if let Some(sym_section) = elf.section_headers.get(sym.st_shndx) {
+            sym_section
+        } else {
+            continue;
+        };
+
+        // Labels of hand-written assembly (e.g. `global_asm!`) often don't have a type, so
+        // global symbols without one in an executable section are assumed to be functions too.
+        let is_untyped_function = sym.st_type() == STT_NOTYPE
+            && (sym.st_bind() == STB_GLOBAL || sym.st_bind() == STB_WEAK)
+            && sym_section.is_executable();
+        if !sym.is_function() && !is_untyped_function {
+            continue;
+        }
+
+        let sym_name = if let Some(name) = strtab.get(sym.st_name).transpose()? {
             name
         } else {
             continue;
@@ -38,21 +84,41 @@ pub fn analyze_elf<'a>(
 
         let sym_name_demangled = demangle_name(sym_name);
 
-        let (section_offset, section_addr) = {
-            let sym_section = elf
-                .section_headers
-                .get(sym.st_shndx)
-                .expect("[FIXME] no matching section header");
-            (sym_section.sh_offset, sym_section.sh_addr)
-        };
+        let (section_offset, section_addr) = (sym_section.sh_offset, sym_section.sh_addr);
 
         // FIXME clamp values to section bounds.
-        // FIXME This works for executable and shared objects that use st_value as a virtual
-        // address to the symbol, but I also want to handle relocatable files, in which case
-        // st_value would hold a section offset for the symbol.
-        let sym_addr = sym.st_value;
+        // In relocatable files st_value is an offset into the symbol's section instead of a
+        // virtual address.
+        let mut sym_addr = if is_relocatable {
+            section_addr + sym.st_value
+        } else {
+            sym.st_value
+        };
+
+        // The lowest bit of an ARM function's address is set if the function contains Thumb code.
+        if arch == BinaryArch::ARM {
+            let kind = if sym_addr & 1 != 0 {
+                MappingKind::Thumb
+            } else {
+                MappingKind::Code
+            };
+            sym_addr &= !1;
+            mappings.push((sym_addr, kind));
+        }
+
+        if arch == BinaryArch::PowerPC64 {
+            let local_entry_offset = ppc64_local_entry_offset(sym.st_other);
+            if local_entry_offset != 0 {
+                local_entries.push((sym_addr + local_entry_offset, sym_addr));
+            }
+        }
+
         let sym_offset = (sym_addr - section_addr) + section_offset;
 
+        if sym.st_size == 0 {
+            unsized_symbols.push((symbols.len(), section_addr + sym_section.sh_size));
+        }
+
         symbols.push(Symbol {
             original_name: Cow::from(sym_name),
             demangled_name: sym_name_demangled,
@@ -61,60 +127,1221 @@ pub fn analyze_elf<'a>(
             addr: sym_addr,
             offset: sym_offset as usize,
             size: sym.st_size as usize,
+            size_inferred: false,
+            archive_member: None,
         });
     }
 
+    infer_symbol_sizes(&mut symbols, &unsized_symbols);
+
+    // Mapping symbols were added first so they take precedence over the ones derived from
+    // function addresses.
+    mappings.sort_by_key(|m| m.0);
+    mappings.dedup_by_key(|m| m.0);
+    local_entries.sort_unstable_by_key(|e| e.0);
+
+    if is_relocatable {
+        get_code_relocations(&elf, &mut relocations)?;
+        relocations.sort_by_key(|r: &Relocation| r.addr);
+    }
+
+    let got_slots = get_got_slots(&elf)?;
+    get_plt_symbols(&elf, data.data(), arch, &got_slots, &mut symbols)?;
+
+    if is_stripped {
+        get_eh_frame_symbols(&elf, data.data(), endian, bits, &mut symbols)?;
+    }
+    let import_symbols = got_slots
+        .iter()
+        .filter_map(|&(slot_addr, name)| {
+            Some(Symbol {
+                original_name: Cow::from(format!("{}@GOT", name)),
+                demangled_name: Cow::from(format!("{}@GOT", demangle_name(name))),
+                short_demangled_name: Default::default(),
+
+                addr: slot_addr,
+                offset: section_addr_to_offset(&elf, slot_addr)?,
+                size: if bits == BinaryBits::Bits64 { 8 } else { 4 },
+                size_inferred: false,
+                archive_member: None,
+            })
+        })
+        .collect();
+
+    let debug = find_separate_debug_info(&elf, data.data(), endian, binary_path, debug_file)?;
+
     Ok(Binary {
         data,
         bits,
         arch,
         endian,
         symbols,
-        object: ObjectExt::Elf(elf),
+        mappings,
+        local_entries,
+        relocations,
+        import_symbols,
+        object: ObjectExt::Elf(ElfExt {
+            elf,
+            path: binary_path.to_path_buf(),
+            debug,
+        }),
     })
 }
 
+/// Sections in a relocatable file don't have an address yet, so they are laid out one after the
+/// other (as a linker would) to give every symbol a unique address. The DWARF sections get their
+/// relocations applied against these addresses by `get_relocated_debug_section`.
+fn layout_relocatable_sections(elf: &mut Elf) {
+    let mut next_addr = 0;
+    for section in elf.section_headers.iter_mut() {
+        if !section.is_alloc() {
+            continue;
+        }
+
+        let align = std::cmp::max(section.sh_addralign, 1);
+        next_addr = (next_addr + align - 1) & !(align - 1);
+        section.sh_addr = next_addr;
+        next_addr += section.sh_size;
+    }
+}
+
+/// Collects the relocations that apply to executable sections of a relocatable file.
+fn get_code_relocations<'a>(
+    elf: &Elf<'a>,
+    relocations: &mut Vec<Relocation<'a>>,
+) -> anyhow::Result<()> {
+    use goblin::elf::reloc::r_to_str;
+    use goblin::elf::sym::STT_SECTION;
+
+    for (reloc_section_idx, reloc_section) in elf.shdr_relocs.iter() {
+        // sh_info holds the index of the section that the relocations apply to.
+        let target_idx = elf.section_headers[*reloc_section_idx].sh_info as usize;
+        let target_section = match elf.section_headers.get(target_idx) {
+            Some(target) if target.is_executable() => target,
+            _ => continue,
+        };
+
+        for reloc in reloc_section.iter() {
+            let sym = if let Some(sym) = elf.syms.get(reloc.r_sym) {
+                sym
+            } else {
+                continue;
+            };
+
+            let sym_name = if sym.st_type() == STT_SECTION {
+                elf.section_headers
+                    .get(sym.st_shndx)
+                    .and_then(|section| elf.shdr_strtab.get(section.sh_name))
+                    .transpose()?
+            } else {
+                elf.strtab.get(sym.st_name).transpose()?
+            };
+
+            // goblin leaves the `R_` prefix off of the names of the relocation types it knows.
+            let kind = r_to_str(reloc.r_type, elf.header.e_machine);
+            let kind = if kind.starts_with("R_") {
+                Cow::from(kind)
+            } else {
+                Cow::from(format!("R_{}", kind))
+            };
+
+            relocations.push(Relocation {
+                addr: target_section.sh_addr + reloc.r_offset,
+                kind,
+                pc_relative: is_pc_relative_relocation(elf.header.e_machine, reloc.r_type),
+                symbol: demangle_name(sym_name.unwrap_or("")),
+                addend: reloc.r_addend.unwrap_or(0),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if a relocation of type `r_type` writes an address relative to the address of the
+/// bytes that it patches.
+fn is_pc_relative_relocation(e_machine: u16, r_type: u32) -> bool {
+    use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64};
+    use goblin::elf::reloc::{
+        R_386_GOTPC, R_386_PC16, R_386_PC32, R_386_PC8, R_386_PLT32, R_AARCH64_ADR_GOT_PAGE,
+        R_AARCH64_ADR_PREL_LO21, R_AARCH64_ADR_PREL_PG_HI21, R_AARCH64_CALL26, R_AARCH64_CONDBR19,
+        R_AARCH64_JUMP26, R_AARCH64_LD_PREL_LO19, R_AARCH64_PREL32, R_AARCH64_PREL64,
+        R_AARCH64_TSTBR14, R_ARM_CALL, R_ARM_JUMP24, R_ARM_PC24, R_ARM_PREL31, R_ARM_REL32,
+        R_ARM_THM_JUMP24, R_ARM_THM_PC22, R_X86_64_GOTPC32, R_X86_64_GOTPC32_TLSDESC,
+        R_X86_64_GOTPCREL, R_X86_64_GOTPCRELX, R_X86_64_GOTTPOFF, R_X86_64_PC16, R_X86_64_PC32,
+        R_X86_64_PC64, R_X86_64_PC8, R_X86_64_PLT32, R_X86_64_REX_GOTPCRELX, R_X86_64_TLSGD,
+        R_X86_64_TLSLD,
+    };
+
+    match e_machine {
+        EM_X86_64 => matches!(
+            r_type,
+            R_X86_64_PC32
+                | R_X86_64_PLT32
+                | R_X86_64_GOTPCREL
+                | R_X86_64_PC16
+                | R_X86_64_PC8
+                | R_X86_64_TLSGD
+                | R_X86_64_TLSLD
+                | R_X86_64_GOTTPOFF
+                | R_X86_64_PC64
+                | R_X86_64_GOTPC32
+                | R_X86_64_GOTPC32_TLSDESC
+                | R_X86_64_GOTPCRELX
+                | R_X86_64_REX_GOTPCRELX
+        ),
+        EM_386 => matches!(
+            r_type,
+            R_386_PC32 | R_386_PLT32 | R_386_GOTPC | R_386_PC16 | R_386_PC8
+        ),
+        EM_AARCH64 => matches!(
+            r_type,
+            R_AARCH64_PREL64
+                | R_AARCH64_PREL32
+                | R_AARCH64_LD_PREL_LO19
+                | R_AARCH64_ADR_PREL_LO21
+                | R_AARCH64_ADR_PREL_PG_HI21
+                | R_AARCH64_TSTBR14
+                | R_AARCH64_CONDBR19
+                | R_AARCH64_JUMP26
+                | R_AARCH64_CALL26
+                | R_AARCH64_ADR_GOT_PAGE
+        ),
+        EM_ARM => matches!(
+            r_type,
+            R_ARM_PC24
+                | R_ARM_REL32
+                | R_ARM_THM_PC22
+                | R_ARM_CALL
+                | R_ARM_JUMP24
+                | R_ARM_THM_JUMP24
+                | R_ARM_PREL31
+        ),
+        _ => false,
+    }
+}
+
+/// Adds a `sub_<addr>` symbol for each function described by an FDE in `.eh_frame` that doesn't
+/// overlap one of the existing symbols. Compilers emit unwind information for
+/// almost every function so this recovers most of the functions of a stripped binary.
+fn get_eh_frame_symbols(
+    elf: &Elf,
+    data: &[u8],
+    endian: BinaryEndian,
+    bits: BinaryBits,
+    symbols: &mut Vec<Symbol>,
+) -> anyhow::Result<()> {
+    use gimli::UnwindSection as _;
+
+    let mut bases = gimli::BaseAddresses::default();
+    let mut eh_frame_section = None;
+    for section in elf.section_headers.iter() {
+        match elf.shdr_strtab.get(section.sh_name).transpose()? {
+            Some(".eh_frame") => {
+                bases = bases.set_eh_frame(section.sh_addr);
+                eh_frame_section = Some(section);
+            }
+            Some(".text") => bases = bases.set_text(section.sh_addr),
+            Some(".got") => bases = bases.set_got(section.sh_addr),
+            _ => continue,
+        }
+    }
+
+    let eh_frame_data = match eh_frame_section.and_then(|section| data.get(section.file_range())) {
+        Some(eh_frame_data) => eh_frame_data,
+        None => return Ok(()),
+    };
+    let endian = if endian == BinaryEndian::Little {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+    let mut eh_frame = gimli::EhFrame::new(eh_frame_data, endian);
+    eh_frame.set_address_size(if bits == BinaryBits::Bits64 { 8 } else { 4 });
+
+    let known_ranges = SymbolRanges::new(symbols);
+
+    // A malformed FDE only means that its function doesn't get a symbol, but the entries after
+    // an entry whose length can't be read can't be found anymore.
+    let mut entries = eh_frame.entries(&bases);
+    while let Ok(Some(entry)) = entries.next() {
+        let fde = match entry {
+            gimli::CieOrFde::Fde(partial) => match partial.parse(gimli::EhFrame::cie_from_offset) {
+                Ok(fde) => fde,
+                Err(_) => continue,
+            },
+            gimli::CieOrFde::Cie(_) => continue,
+        };
+
+        let addr = fde.initial_address();
+        if fde.len() == 0 || known_ranges.overlaps(addr, addr + fde.len()) {
+            continue;
+        }
+
+        let offset = if let Some(offset) = section_addr_to_offset(elf, addr) {
+            offset
+        } else {
+            continue;
+        };
+
+        let name = format!("sub_{:x}", addr);
+        symbols.push(Symbol {
+            original_name: Cow::from(name.clone()),
+            demangled_name: Cow::from(name),
+            short_demangled_name: Default::default(),
+
+            addr,
+            offset,
+            size: fde.len() as usize,
+            size_inferred: false,
+            archive_member: None,
+        });
+    }
+
+    Ok(())
+}
+
+/// The address ranges of symbols, used to check whether an address range overlaps any of them.
+struct SymbolRanges {
+    /// Ranges sorted by their start address. Symbols without a size cover a single byte.
+    ranges: Vec<(u64, u64)>,
+
+    /// The largest end address of the ranges up to and including each range, because a range
+    /// can also contain the ranges after it.
+    max_ends: Vec<u64>,
+}
+
+impl SymbolRanges {
+    fn new(symbols: &[Symbol]) -> SymbolRanges {
+        let mut ranges: Vec<(u64, u64)> = symbols
+            .iter()
+            .map(|sym| (sym.addr, sym.addr + sym.size.max(1) as u64))
+            .collect();
+        ranges.sort_unstable();
+
+        let mut max_end = 0;
+        let max_ends = ranges
+            .iter()
+            .map(|range| {
+                max_end = max_end.max(range.1);
+                max_end
+            })
+            .collect();
+
+        SymbolRanges { ranges, max_ends }
+    }
+
+    /// Returns true if any of the ranges overlaps `start..end`.
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        // The number of ranges that start before `end`.
+        let count = self
+            .ranges
+            .binary_search_by(|r| r.0.cmp(&end).then(std::cmp::Ordering::Greater))
+            .unwrap_or_else(|idx| idx);
+        count > 0 && self.max_ends[count - 1] > start
+    }
+}
+
+/// Returns the address of each GOT slot that the dynamic linker fills in with the address of a
+/// named dynamic symbol along with the symbol's name, sorted by address.
+fn get_got_slots<'a>(elf: &Elf<'a>) -> anyhow::Result<Vec<(u64, &'a str)>> {
+    let mut slots = Vec::new();
+
+    // Dynamic relocations also initialize things like function pointers in `.data` which
+    // shouldn't be displayed as GOT slots.
+    let mut got_ranges = Vec::new();
+    for section in elf.section_headers.iter() {
+        if let Some(".got") | Some(".got.plt") = elf.shdr_strtab.get(section.sh_name).transpose()? {
+            got_ranges.push(section.sh_addr..(section.sh_addr + section.sh_size));
+        }
+    }
+
+    let relocs = elf
+        .pltrelocs
+        .iter()
+        .chain(elf.dynrelas.iter())
+        .chain(elf.dynrels.iter());
+    for reloc in relocs.filter(|reloc| reloc.r_sym != 0) {
+        if !got_ranges
+            .iter()
+            .any(|range| range.contains(&reloc.r_offset))
+        {
+            continue;
+        }
+
+        let sym = if let Some(sym) = elf.dynsyms.get(reloc.r_sym) {
+            sym
+        } else {
+            continue;
+        };
+
+        match elf.dynstrtab.get(sym.st_name).transpose()? {
+            Some(name) if !name.is_empty() => slots.push((reloc.r_offset, name)),
+            _ => continue,
+        }
+    }
+
+    slots.sort_by_key(|slot| slot.0);
+    slots.dedup_by_key(|slot| slot.0);
+    Ok(slots)
+}
+
+/// Adds a `foo@plt` symbol for each PLT stub that jumps through the GOT slot of the dynamic
+/// symbol `foo`. Rather than relying on the order of the stubs matching the order of the
+/// `.rela.plt` entries, the GOT slot is decoded from the stub's indirect jump which also works for
+/// the `.plt.sec` and `.plt.got` sections.
+fn get_plt_symbols<'a>(
+    elf: &Elf<'a>,
+    data: &[u8],
+    arch: BinaryArch,
+    got_slots: &[(u64, &'a str)],
+    symbols: &mut Vec<Symbol<'a>>,
+) -> anyhow::Result<()> {
+    if got_slots.is_empty() {
+        return Ok(());
+    }
+
+    // 32-bit x86 PIC stubs address the GOT relative to the start of `.got.plt`, which is where
+    // `ebx` points.
+    let mut got_plt_addr = 0;
+    for section in elf.section_headers.iter() {
+        if elf.shdr_strtab.get(section.sh_name).transpose()? == Some(".got.plt") {
+            got_plt_addr = section.sh_addr;
+        }
+    }
+
+    for section in elf.section_headers.iter() {
+        let default_entry_size = match elf.shdr_strtab.get(section.sh_name).transpose()? {
+            Some(".plt") | Some(".plt.sec") => 16,
+            Some(".plt.got") if arch == BinaryArch::AMD64 => 8,
+            Some(".plt.got") => 16,
+            _ => continue,
+        };
+        let entry_size = if section.sh_entsize != 0 {
+            section.sh_entsize
+        } else {
+            default_entry_size
+        };
+
+        let section_data = if let Some(section_data) = data.get(section.file_range()) {
+            section_data
+        } else {
+            continue;
+        };
+
+        for (idx, stub) in section_data.chunks_exact(entry_size as usize).enumerate() {
+            let stub_addr = section.sh_addr + idx as u64 * entry_size;
+            let slot_addr = match plt_stub_got_slot(arch, stub, stub_addr, got_plt_addr) {
+                Some(slot_addr) => slot_addr,
+                None => continue,
+            };
+
+            if let Ok(slot_idx) = got_slots.binary_search_by_key(&slot_addr, |slot| slot.0) {
+                let name = got_slots[slot_idx].1;
+                symbols.push(Symbol {
+                    original_name: Cow::from(format!("{}@plt", name)),
+                    demangled_name: Cow::from(format!("{}@plt", demangle_name(name))),
+                    short_demangled_name: Default::default(),
+
+                    addr: stub_addr,
+                    offset: (section.sh_offset + idx as u64 * entry_size) as usize,
+                    size: entry_size as usize,
+                    size_inferred: false,
+                    archive_member: None,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes the indirect jump of a PLT stub and returns the address of the GOT slot that it jumps
+/// through. The header at the start of `.plt` doesn't jump through the slot of a symbol so it is
+/// never matched to one.
+fn plt_stub_got_slot(
+    arch: BinaryArch,
+    stub: &[u8],
+    stub_addr: u64,
+    got_plt_addr: u64,
+) -> Option<u64> {
+    match arch {
+        BinaryArch::AMD64 | BinaryArch::X86 => {
+            // Stubs built with `-z ibt` start with an `endbr64` or `endbr32` and the jump might
+            // have a `bnd` prefix.
+            let mut start = 0;
+            if stub.starts_with(&[0xF3, 0x0F, 0x1E, 0xFA])
+                || stub.starts_with(&[0xF3, 0x0F, 0x1E, 0xFB])
+            {
+                start += 4;
+            }
+            if stub.get(start) == Some(&0xF2) {
+                start += 1;
+            }
+
+            let disp = LittleEndian::read_i32(stub.get((start + 2)..(start + 6))?) as i64 as u64;
+            match (arch, stub.get(start..(start + 2))?) {
+                // jmp qword ptr [rip + disp]
+                (BinaryArch::AMD64, [0xFF, 0x25]) => {
+                    Some((stub_addr + start as u64 + 6).wrapping_add(disp))
+                }
+                // jmp dword ptr [disp]
+                (BinaryArch::X86, [0xFF, 0x25]) => Some(disp & 0xFFFF_FFFF),
+                // jmp dword ptr [ebx + disp]
+                (BinaryArch::X86, [0xFF, 0xA3]) => {
+                    Some(got_plt_addr.wrapping_add(disp) & 0xFFFF_FFFF)
+                }
+                _ => None,
+            }
+        }
+
+        BinaryArch::AArch64 => {
+            // adrp x16, page
+            // ldr  x17, [x16, offset]
+            let mut words = stub.chunks_exact(4).map(LittleEndian::read_u32);
+            let mut adrp_addr = stub_addr;
+            let mut adrp = words.next()?;
+            if adrp == 0xD503_245F {
+                // bti c
+                adrp = words.next()?;
+                adrp_addr += 4;
+            }
+            let ldr = words.next()?;
+            if adrp & 0x9F00_001F != 0x9000_0010 || ldr & 0xFFC0_03FF != 0xF940_0211 {
+                return None;
+            }
+
+            let immlo = (adrp >> 29) & 0x3;
+            let immhi = (adrp >> 5) & 0x7_FFFF;
+            let page_delta = ((((immhi << 2) | immlo) << 11) as i32 as i64) << 1;
+            let page = (adrp_addr & !0xFFF).wrapping_add(page_delta as u64);
+            Some(page + ((ldr >> 10) & 0xFFF) as u64 * 8)
+        }
+
+        _ => None,
+    }
+}
+
+/// Collects the `$a`, `$t` and `$d` mapping symbols that ARM toolchains use to mark the start of
+/// ARM code, Thumb code and data (literal pools) within a section.
+fn get_arm_mapping_symbols(
+    elf: &Elf,
+    mappings: &mut Vec<(u64, MappingKind)>,
+) -> anyhow::Result<()> {
+    for sym in elf.syms.iter() {
+        let sym_name = if let Some(name) = elf.strtab.get(sym.st_name).transpose()? {
+            name
+        } else {
+            continue;
+        };
+
+        // Mapping symbols may also have a suffix like `$d.12`.
+        let kind = match sym_name.split('.').next() {
+            Some("$a") => MappingKind::Code,
+            Some("$t") => MappingKind::Thumb,
+            Some("$d") => MappingKind::Data,
+            _ => continue,
+        };
+
+        let addr = if elf.header.e_type == goblin::elf::header::ET_REL {
+            elf.section_headers
+                .get(sym.st_shndx)
+                .map(|section| section.sh_addr)
+                .unwrap_or(0)
+                + sym.st_value
+        } else {
+            sym.st_value
+        };
+
+        mappings.push((addr, kind));
+    }
+
+    Ok(())
+}
+
+/// Returns the distance between the global and local entry points of a PPC64 ELFv2 function,
+/// which is encoded in the 3 most significant bits of the symbol's `st_other` field.
+fn ppc64_local_entry_offset(st_other: u8) -> u64 {
+    match st_other >> 5 {
+        // 0 and 1 mean that both entry points are the same, 7 is reserved.
+        value @ 2..=6 => 1 << value,
+        _ => 0,
+    }
+}
+
 pub(super) fn elf_line_mapper<'a>(
-    elf: &'a Elf<'a>,
+    elf: &'a ElfExt<'a>,
     endian: BinaryEndian,
-    data: &'a [u8],
+    binary_data: &'a BinaryData,
     base_directory: &Path,
     resolve_strategy: FileResolveStrategy,
 ) -> anyhow::Result<Box<dyn 'a + LineMapper>> {
+    let split_dwarf = SplitDwarf::new(&elf.path, endian);
+
+    if let ElfDebug::External(ref external) = elf.debug {
+        if let Some((debug_path, debug_data)) = select_separate_debug_file(external) {
+            binary_data.set_dwarf_data(debug_data);
+
+            // The same lifetime casting as for external Mach-O DWARF files. The data is never
+            // moved or reassigned once it has been set.
+            let dwarf_data_ref = binary_data.dwarf_data();
+            let dwarf_data_slice =
+                unsafe { std::mem::transmute::<&'_ [u8], &'a [u8]>(&*dwarf_data_ref) };
+            let debug_elf = Elf::parse(dwarf_data_slice)?;
+            let sup =
+                load_supplementary_file(&debug_elf, dwarf_data_slice, &debug_path, binary_data)?;
+
+            return elf_dwarf_line_mapper(
+                |name| get_stored_debug_section(&debug_elf, dwarf_data_slice, name, binary_data),
+                |name| match sup {
+                    Some((ref sup_elf, sup_data)) => {
+                        get_stored_debug_section(sup_elf, sup_data, name, binary_data)
+                    }
+                    None => Ok(&[]),
+                },
+                split_dwarf,
+                false,
+                endian,
+                base_directory,
+                resolve_strategy,
+            );
+        }
+    }
+
+    if elf.elf.header.e_type == goblin::elf::header::ET_REL {
+        if let Some(r_type) = unsupported_debug_relocation(&elf.elf) {
+            eprintln!(
+                "warning: source lines are not available because relocations of type {} in the debug info are not supported.",
+                goblin::elf::reloc::r_to_str(r_type, elf.elf.header.e_machine)
+            );
+            return Ok(Box::new(super::NoOpLineMapper));
+        }
+
+        return elf_dwarf_line_mapper(
+            |name| get_relocated_debug_section(&elf.elf, binary_data.data(), name, binary_data),
+            |_name| Ok(&[]),
+            split_dwarf,
+            true,
+            endian,
+            base_directory,
+            resolve_strategy,
+        );
+    }
+
+    let sup = load_supplementary_file(&elf.elf, binary_data.data(), &elf.path, binary_data)?;
+    elf_dwarf_line_mapper(
+        |name| get_stored_debug_section(&elf.elf, binary_data.data(), name, binary_data),
+        |name| match sup {
+            Some((ref sup_elf, sup_data)) => {
+                get_stored_debug_section(sup_elf, sup_data, name, binary_data)
+            }
+            None => Ok(&[]),
+        },
+        split_dwarf,
+        false,
+        endian,
+        base_directory,
+        resolve_strategy,
+    )
+}
+
+/// Creates a line mapper from DWARF sections, which come from either the binary itself or a
+/// separate debug file, and the sections of its supplementary file.
+fn elf_dwarf_line_mapper<'a, F, S>(
+    section_data: F,
+    sup_section_data: S,
+    split_dwarf: SplitDwarf,
+    relocatable: bool,
+    endian: BinaryEndian,
+    base_directory: &Path,
+    resolve_strategy: FileResolveStrategy,
+) -> anyhow::Result<Box<dyn 'a + LineMapper>>
+where
+    F: Fn(&str) -> anyhow::Result<&'a [u8]>,
+    S: Fn(&str) -> anyhow::Result<&'a [u8]>,
+{
     let mapper: Box<dyn LineMapper> = if endian == BinaryEndian::Little {
         let loader = |section: gimli::SectionId| {
-            get_section_by_name(elf, data, section.name())
+            section_data(section.name()).map(|d| gimli::EndianSlice::new(d, gimli::LittleEndian))
+        };
+        let sup_loader = |section: gimli::SectionId| {
+            sup_section_data(section.name())
                 .map(|d| gimli::EndianSlice::new(d, gimli::LittleEndian))
         };
-        let sup_loader =
-            |_section: gimli::SectionId| Ok(gimli::EndianSlice::new(&[], gimli::LittleEndian));
 
-        Box::new(DwarfLineMapper::new(
-            loader,
-            sup_loader,
-            base_directory,
-            resolve_strategy,
-        )?)
+        let mapper = DwarfLineMapper::new(loader, sup_loader, base_directory, resolve_strategy)?
+            .with_split_dwarf(split_dwarf);
+        if relocatable {
+            Box::new(mapper.with_relocatable_addresses())
+        } else {
+            Box::new(mapper)
+        }
     } else {
-        let loader = move |section: gimli::SectionId| {
-            get_section_by_name(&elf, data, section.name())
-                .map(|d| gimli::EndianSlice::new(d, gimli::BigEndian))
+        let loader = |section: gimli::SectionId| {
+            section_data(section.name()).map(|d| gimli::EndianSlice::new(d, gimli::BigEndian))
+        };
+        let sup_loader = |section: gimli::SectionId| {
+            sup_section_data(section.name()).map(|d| gimli::EndianSlice::new(d, gimli::BigEndian))
         };
-        let sup_loader =
-            |_section: gimli::SectionId| Ok(gimli::EndianSlice::new(&[], gimli::BigEndian));
 
-        Box::new(DwarfLineMapper::new(
-            loader,
-            sup_loader,
-            base_directory,
-            resolve_strategy,
-        )?)
+        let mapper = DwarfLineMapper::new(loader, sup_loader, base_directory, resolve_strategy)?
+            .with_split_dwarf(split_dwarf);
+        if relocatable {
+            Box::new(mapper.with_relocatable_addresses())
+        } else {
+            Box::new(mapper)
+        }
     };
 
     Ok(mapper)
 }
 
-fn get_section_by_name<'a>(
+/// Loads the supplementary file named by the `.gnu_debugaltlink` section of `elf` (written by
+/// `dwz`), which contains DWARF that is shared between several binaries. The section contains a
+/// null terminated path, which is relative to the directory of the file containing the section,
+/// followed by the build ID of the supplementary file.
+fn load_supplementary_file<'a>(
+    elf: &Elf,
+    data: &[u8],
+    path: &Path,
+    binary_data: &'a BinaryData,
+) -> anyhow::Result<Option<(Elf<'a>, &'a [u8])>> {
+    let section = get_section_by_name(elf, data, ".gnu_debugaltlink")?;
+    let name_len = if let Some(name_len) = section.iter().position(|&b| b == 0) {
+        name_len
+    } else {
+        return Ok(None);
+    };
+    let alt_name = PathBuf::from(String::from_utf8_lossy(&section[..name_len]).into_owned());
+    let alt_build_id = &section[(name_len + 1)..];
+
+    let mut candidates = Vec::new();
+    if let Some(directory) = path.parent() {
+        candidates.push(directory.join(&alt_name));
+    }
+    if let Some(build_id_path) = build_id_debug_path(alt_build_id) {
+        candidates.push(build_id_path);
+    }
+
+    for candidate in candidates.iter() {
+        let sup_data = if let Ok(sup_data) = std::fs::read(candidate) {
+            sup_data
+        } else {
+            continue;
+        };
+
+        let matches = Elf::parse(&sup_data)
+            .ok()
+            .and_then(|sup_elf| get_build_id(&sup_elf, &sup_data))
+            .map(|build_id| alt_build_id.is_empty() || build_id == alt_build_id)
+            .unwrap_or(false);
+        if !matches {
+            eprintln!(
+                "warning: ignoring supplementary debug file `{}` because it does not match.",
+                candidate.display()
+            );
+            continue;
+        }
+
+        binary_data.set_sup_dwarf_data(sup_data);
+        let sup_data_ref = binary_data.sup_dwarf_data();
+        let sup_data_slice = unsafe { std::mem::transmute::<&'_ [u8], &'a [u8]>(&*sup_data_ref) };
+        return Ok(Some((Elf::parse(sup_data_slice)?, sup_data_slice)));
+    }
+
+    eprintln!(
+        "warning: supplementary debug file `{}` was not found.",
+        alt_name.display()
+    );
+    Ok(None)
+}
+
+/// The global directory that GDB and distributions install separate debug files into.
+const GLOBAL_DEBUG_DIRECTORY: &str = "/usr/lib/debug";
+
+/// Looks for a separate file containing the DWARF debug information of a binary that has been
+/// stripped (e.g. with `objcopy --only-keep-debug`). This uses the same places as GDB:
+///
+/// - `/usr/lib/debug/.build-id/xx/yyyy.debug` using the binary's `.note.gnu.build-id`.
+/// - The file named in `.gnu_debuglink` in the binary's directory, in a `.debug` directory next to
+///   the binary and in `/usr/lib/debug/[binary directory]`.
+/// - `[binary].debug` next to the binary.
+///
+/// A file passed with `--debug-file` is always used first. The files are only checked against the
+/// binary's build ID or debug link CRC once the debug information is needed.
+fn find_separate_debug_info(
+    elf: &Elf,
+    data: &[u8],
+    endian: BinaryEndian,
+    binary_path: &Path,
+    debug_file: Option<&Path>,
+) -> anyhow::Result<ElfDebug> {
+    // Relocatable objects (including archive members) never have their debug info split off.
+    if debug_file.is_none()
+        && (elf.header.e_type == goblin::elf::header::ET_REL
+            || has_debug_section(elf, ".debug_line")?)
+    {
+        return Ok(ElfDebug::Internal);
+    }
+
+    let build_id = get_build_id(elf, data);
+    let debug_link = get_debug_link(elf, data, endian)?;
+
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if let Some(debug_file) = debug_file {
+        candidates.push(debug_file.to_path_buf());
+    }
+
+    if let Some(path) = build_id.as_ref().and_then(|id| build_id_debug_path(id)) {
+        candidates.push(path);
+    }
+
+    let binary_path = binary_path
+        .canonicalize()
+        .unwrap_or_else(|_| binary_path.to_path_buf());
+    if let Some(directory) = binary_path.parent() {
+        if let Some((ref name, _)) = debug_link {
+            candidates.push(directory.join(name));
+            candidates.push(directory.join(".debug").join(name));
+
+            // Path::join would replace the global directory with an absolute directory.
+            let mut global = PathBuf::from(GLOBAL_DEBUG_DIRECTORY);
+            global.extend(directory.strip_prefix("/").unwrap_or(directory));
+            global.push(name);
+            candidates.push(global);
+        }
+
+        if let Some(file_name) = binary_path.file_name() {
+            let mut name = file_name.to_os_string();
+            name.push(".debug");
+            candidates.push(directory.join(name));
+        }
+    }
+
+    // The binary itself can end up in this list if its debug link names itself.
+    candidates.retain(|candidate| candidate.is_file() && *candidate != binary_path);
+    candidates.dedup();
+
+    if candidates.is_empty() {
+        return Ok(ElfDebug::Internal);
+    }
+
+    Ok(ElfDebug::External(ElfExternalDebug {
+        candidates,
+        build_id,
+        debug_link_crc: debug_link.map(|(_, crc)| crc),
+    }))
+}
+
+/// Reads the first separate debug file that matches the binary's build ID or, if the binary
+/// doesn't have one, the CRC from its debug link. Files that don't match are skipped so that
+/// source lines from a stale build are never shown.
+fn select_separate_debug_file(external: &ElfExternalDebug) -> Option<(PathBuf, Vec<u8>)> {
+    for candidate in external.candidates.iter() {
+        let debug_data = if let Ok(debug_data) = std::fs::read(candidate) {
+            debug_data
+        } else {
+            continue;
+        };
+
+        let matches = if let Some(ref build_id) = external.build_id {
+            Elf::parse(&debug_data)
+                .ok()
+                .and_then(|debug_elf| get_build_id(&debug_elf, &debug_data))
+                .map(|debug_build_id| debug_build_id == *build_id)
+                .unwrap_or(false)
+        } else if let Some(crc) = external.debug_link_crc {
+            crc32(&debug_data) == crc
+        } else {
+            true
+        };
+
+        if matches {
+            return Some((candidate.clone(), debug_data));
+        }
+
+        eprintln!(
+            "warning: ignoring debug file `{}` because it does not match the binary.",
+            candidate.display()
+        );
+    }
+
+    None
+}
+
+/// Returns `/usr/lib/debug/.build-id/xx/yyyy.debug` for a build ID.
+fn build_id_debug_path(build_id: &[u8]) -> Option<PathBuf> {
+    if build_id.len() < 2 {
+        return None;
+    }
+
+    let mut path = PathBuf::from(GLOBAL_DEBUG_DIRECTORY);
+    path.push(".build-id");
+    path.push(hex_string(&build_id[..1]));
+    path.push(format!("{}.debug", hex_string(&build_id[1..])));
+    Some(path)
+}
+
+/// Returns the contents of the binary's `NT_GNU_BUILD_ID` note.
+fn get_build_id(elf: &Elf, data: &[u8]) -> Option<Vec<u8>> {
+    use goblin::elf::note::NT_GNU_BUILD_ID;
+
+    elf.iter_note_sections(data, Some(".note.gnu.build-id"))?
+        .filter_map(Result::ok)
+        .find(|note| note.n_type == NT_GNU_BUILD_ID && note.name.starts_with("GNU"))
+        .map(|note| note.desc.to_vec())
+}
+
+/// Returns the file name and CRC stored in the `.gnu_debuglink` section. The section contains a
+/// null terminated file name, padding up to a 4 byte boundary and then the CRC32 of the debug
+/// file.
+fn get_debug_link(
+    elf: &Elf,
+    data: &[u8],
+    endian: BinaryEndian,
+) -> anyhow::Result<Option<(String, u32)>> {
+    use byteorder::{BigEndian, ByteOrder as _, LittleEndian};
+
+    let section = get_section_by_name(elf, data, ".gnu_debuglink")?;
+    let name_len = if let Some(name_len) = section.iter().position(|&b| b == 0) {
+        name_len
+    } else {
+        return Ok(None);
+    };
+
+    let crc_offset = (name_len + 1 + 3) & !3;
+    let crc = if let Some(crc) = section.get(crc_offset..(crc_offset + 4)) {
+        crc
+    } else {
+        return Ok(None);
+    };
+
+    let crc = if endian == BinaryEndian::Little {
+        LittleEndian::read_u32(crc)
+    } else {
+        BigEndian::read_u32(crc)
+    };
+
+    Ok(Some((
+        String::from_utf8_lossy(&section[..name_len]).into_owned(),
+        crc,
+    )))
+}
+
+/// The CRC32 (IEEE) used by `.gnu_debuglink`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(!0u32, |crc, &b| {
+        table[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(super) fn elf_addr_to_offset(elf: &ElfExt, addr: u64) -> Option<usize> {
+    section_addr_to_offset(&elf.elf, addr)
+}
+
+fn section_addr_to_offset(elf: &Elf, addr: u64) -> Option<usize> {
+    use goblin::elf::section_header::SHT_NOBITS;
+
+    elf.section_headers
+        .iter()
+        .filter(|section| section.is_alloc() && section.sh_type != SHT_NOBITS)
+        .find(|section| addr >= section.sh_addr && addr < (section.sh_addr + section.sh_size))
+        .map(|section| (addr - section.sh_addr + section.sh_offset) as usize)
+}
+
+/// Returns true if the ELF has a non-empty debug section with the given name, which might be
+/// compressed.
+fn has_debug_section(elf: &Elf, name: &str) -> anyhow::Result<bool> {
+    let zname = name.replacen(".debug_", ".zdebug_", 1);
+    for section in elf.section_headers.iter() {
+        if let Some(section_name) = elf.shdr_strtab.get(section.sh_name).transpose()? {
+            if (section_name == name || section_name == zname) && section.sh_size > 0 {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Like `get_debug_section`, but decompressed sections are stored in the `BinaryData` so that
+/// they live as long as the binary's own data.
+fn get_stored_debug_section<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    name: &str,
+    binary_data: &'a BinaryData,
+) -> anyhow::Result<&'a [u8]> {
+    match get_debug_section(elf, data, name)? {
+        Cow::Borrowed(section) => Ok(section),
+        Cow::Owned(section) => Ok(binary_data.store_section(section)),
+    }
+}
+
+/// Returns the size of the value that a relocation in a debug section writes, or `None` if the
+/// relocation isn't one of the absolute relocations that compilers use in debug info.
+fn debug_relocation_size(e_machine: u16, r_type: u32) -> Option<usize> {
+    use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64};
+    use goblin::elf::reloc::{
+        R_386_32, R_AARCH64_ABS32, R_AARCH64_ABS64, R_ARM_ABS32, R_X86_64_32, R_X86_64_32S,
+        R_X86_64_64,
+    };
+
+    match (e_machine, r_type) {
+        (_, 0) => Some(0),
+        (EM_X86_64, R_X86_64_64) | (EM_AARCH64, R_AARCH64_ABS64) => Some(8),
+        (EM_X86_64, R_X86_64_32)
+        | (EM_X86_64, R_X86_64_32S)
+        | (EM_AARCH64, R_AARCH64_ABS32)
+        | (EM_386, R_386_32)
+        | (EM_ARM, R_ARM_ABS32) => Some(4),
+        _ => None,
+    }
+}
+
+/// Returns the type of the first relocation of a debug section that can't be applied.
+fn unsupported_debug_relocation(elf: &Elf) -> Option<u32> {
+    elf.shdr_relocs
+        .iter()
+        .filter(|(reloc_section_idx, _)| {
+            let target_idx = elf.section_headers[*reloc_section_idx].sh_info as usize;
+            elf.section_headers
+                .get(target_idx)
+                .and_then(|target| elf.shdr_strtab.get(target.sh_name))
+                .and_then(Result::ok)
+                .map(|name| name.starts_with(".debug_"))
+                .unwrap_or(false)
+        })
+        .flat_map(|(_, relocs)| relocs.iter())
+        .map(|reloc| reloc.r_type)
+        .find(|&r_type| debug_relocation_size(elf.header.e_machine, r_type).is_none())
+}
+
+/// Returns a debug section of a relocatable file with its relocations applied, which fill in the
+/// addresses of code (using the addresses from `layout_relocatable_sections`) and the offsets
+/// into other debug sections.
+fn get_relocated_debug_section<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    name: &str,
+    binary_data: &'a BinaryData,
+) -> anyhow::Result<&'a [u8]> {
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+    let section_idx = elf.section_headers.iter().position(|section| {
+        elf.shdr_strtab.get(section.sh_name).and_then(Result::ok) == Some(name)
+    });
+    let relocs = section_idx.and_then(|idx| {
+        elf.shdr_relocs
+            .iter()
+            .find(|(reloc_section_idx, _)| {
+                elf.section_headers[*reloc_section_idx].sh_info as usize == idx
+            })
+            .map(|(_, relocs)| relocs)
+    });
+    let relocs = match relocs {
+        Some(relocs) => relocs,
+        None => return get_stored_debug_section(elf, data, name, binary_data),
+    };
+
+    let mut section = get_debug_section(elf, data, name)?.into_owned();
+    for reloc in relocs.iter() {
+        let size = debug_relocation_size(elf.header.e_machine, reloc.r_type).unwrap_or(0);
+        if size == 0 {
+            continue;
+        }
+
+        let offset = reloc.r_offset as usize;
+        let target = section
+            .get_mut(offset..(offset + size))
+            .ok_or_else(|| anyhow::anyhow!("relocation in section `{}` is out of bounds", name))?;
+
+        // Relocation sections without addends (`SHT_REL`) keep the addend in the target.
+        let addend = match (reloc.r_addend, size, elf.little_endian) {
+            (Some(addend), _, _) => addend as u64,
+            (None, 8, true) => LittleEndian::read_u64(target),
+            (None, 8, false) => BigEndian::read_u64(target),
+            (None, _, true) => LittleEndian::read_u32(target) as u64,
+            (None, _, false) => BigEndian::read_u32(target) as u64,
+        };
+
+        // Symbols in relocatable files have an offset into their section as their value.
+        let symbol_addr = match elf.syms.get(reloc.r_sym) {
+            Some(sym) => {
+                let section_addr = elf
+                    .section_headers
+                    .get(sym.st_shndx)
+                    .map(|section| section.sh_addr)
+                    .unwrap_or(0);
+                section_addr + sym.st_value
+            }
+            None => 0,
+        };
+        let value = symbol_addr.wrapping_add(addend);
+
+        match (size, elf.little_endian) {
+            (8, true) => LittleEndian::write_u64(target, value),
+            (8, false) => BigEndian::write_u64(target, value),
+            (_, true) => LittleEndian::write_u32(target, value as u32),
+            (_, false) => BigEndian::write_u32(target, value as u32),
+        }
+    }
+
+    Ok(binary_data.store_section(section))
+}
+
+/// Returns the contents of a debug section, decompressing it if it was compressed with
+/// `--compress-debug-sections` (`SHF_COMPRESSED` with zlib or zstd), or if it's a legacy
+/// `.zdebug_*` section.
+pub(super) fn get_debug_section<'a>(
+    elf: &Elf<'a>,
+    data: &'a [u8],
+    name: &str,
+) -> anyhow::Result<Cow<'a, [u8]>> {
+    use goblin::elf::section_header::SHF_COMPRESSED;
+
+    let zname = name.replacen(".debug_", ".zdebug_", 1);
+    for section in elf.section_headers.iter() {
+        let section_name =
+            if let Some(section_name) = elf.shdr_strtab.get(section.sh_name).transpose()? {
+                section_name
+            } else {
+                continue;
+            };
+
+        if section_name == name {
+            let section_data = data
+                .get(section.file_range())
+                .ok_or_else(|| anyhow::anyhow!("section `{}` is out of bounds", name))?;
+
+            if section.sh_flags & SHF_COMPRESSED as u64 == 0 {
+                return Ok(Cow::Borrowed(section_data));
+            }
+
+            return decompress_section(elf, section_data)
+                .map(Cow::Owned)
+                .with_context(|| format!("failed to decompress section `{}`", name));
+        } else if section_name == zname && name != zname {
+            let section_data = data
+                .get(section.file_range())
+                .ok_or_else(|| anyhow::anyhow!("section `{}` is out of bounds", zname))?;
+
+            return decompress_zdebug_section(section_data)
+                .map(Cow::Owned)
+                .with_context(|| format!("failed to decompress section `{}`", zname));
+        }
+    }
+    Ok(Cow::Borrowed(&[]))
+}
+
+const ELFCOMPRESS_ZLIB: u32 = 1;
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
+/// Decompresses an `SHF_COMPRESSED` section, which starts with an `Elf32_Chdr` or `Elf64_Chdr`
+/// header.
+fn decompress_section(elf: &Elf, section_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+    fn read_chdr<E: ByteOrder>(is_64: bool, data: &[u8]) -> Option<(u32, u64, usize)> {
+        if is_64 {
+            let header = data.get(..24)?;
+            Some((E::read_u32(header), E::read_u64(&header[8..]), 24))
+        } else {
+            let header = data.get(..12)?;
+            Some((E::read_u32(header), E::read_u32(&header[4..]) as u64, 12))
+        }
+    }
+
+    let chdr = if elf.little_endian {
+        read_chdr::<LittleEndian>(elf.is_64, section_data)
+    } else {
+        read_chdr::<BigEndian>(elf.is_64, section_data)
+    };
+    let (ch_type, ch_size, header_size) =
+        chdr.ok_or_else(|| anyhow::anyhow!("compression header is out of bounds"))?;
+    let compressed = &section_data[header_size..];
+
+    match ch_type {
+        ELFCOMPRESS_ZLIB => decompress_zlib(compressed, ch_size as usize),
+        ELFCOMPRESS_ZSTD => decompress_zstd(compressed, ch_size as usize),
+        _ => Err(anyhow::anyhow!("unknown compression type {}", ch_type)),
+    }
+}
+
+/// Decompresses a legacy `.zdebug_*` section, which contains the magic `ZLIB`, the uncompressed
+/// size as a 64-bit big endian integer and then the zlib stream.
+fn decompress_zdebug_section(section_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use byteorder::{BigEndian, ByteOrder as _};
+
+    if section_data.len() < 12 || &section_data[..4] != b"ZLIB" {
+        // Sections that didn't get smaller when they were compressed are stored as they are.
+        return Ok(section_data.to_vec());
+    }
+
+    let size = BigEndian::read_u64(&section_data[4..12]);
+    decompress_zlib(&section_data[12..], size as usize)
+}
+
+fn decompress_zlib(compressed: &[u8], size: usize) -> anyhow::Result<Vec<u8>> {
+    read_decompressed(
+        flate2::read::ZlibDecoder::new(compressed),
+        compressed.len(),
+        size,
+    )
+}
+
+fn decompress_zstd(mut compressed: &[u8], size: usize) -> anyhow::Result<Vec<u8>> {
+    let compressed_len = compressed.len();
+    let decoder = ruzstd::StreamingDecoder::new(&mut compressed).map_err(anyhow::Error::msg)?;
+    read_decompressed(decoder, compressed_len, size)
+}
+
+/// Reads the output of a decoder, which must be exactly the `size` from the section's header.
+/// The header is only trusted as far as a plausible compression ratio goes when allocating, and
+/// reading stops right after `size` bytes so a corrupt header can't make this read forever.
+fn read_decompressed<R: std::io::Read>(
+    decoder: R,
+    compressed_len: usize,
+    size: usize,
+) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read as _;
+
+    const MAX_COMPRESSION_RATIO: usize = 64;
+
+    let mut decompressed = Vec::with_capacity(std::cmp::min(
+        size,
+        compressed_len.saturating_mul(MAX_COMPRESSION_RATIO),
+    ));
+    decoder
+        .take((size as u64).saturating_add(1))
+        .read_to_end(&mut decompressed)?;
+
+    if decompressed.len() != size {
+        return Err(anyhow::anyhow!(
+            "decompressed size does not match the size in the header ({} bytes)",
+            size
+        ));
+    }
+    Ok(decompressed)
+}
+
+pub(super) fn get_section_by_name<'a>(
     elf: &Elf<'a>,
     binary: &'a [u8],
     name: &str,
@@ -128,3 +1355,171 @@ fn get_section_by_name<'a>(
     }
     Ok(&[])
 }
+
+#[derive(Debug)]
+pub struct ElfExt<'a> {
+    elf: Elf<'a>,
+
+    /// Path of the binary, which paths in `.gnu_debugaltlink` are relative to.
+    path: PathBuf,
+
+    debug: ElfDebug,
+}
+
+#[derive(Debug)]
+pub enum ElfDebug {
+    /// DWARF debug information is in the ELF itself.
+    Internal,
+
+    /// The binary was stripped and its DWARF debug information might be in a separate file.
+    External(ElfExternalDebug),
+}
+
+#[derive(Debug)]
+pub struct ElfExternalDebug {
+    /// Possible separate debug files in the order that they are checked.
+    candidates: Vec<PathBuf>,
+
+    build_id: Option<Vec<u8>>,
+    debug_link_crc: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, is_pc_relative_relocation, plt_stub_got_slot, BinaryArch, SymbolRanges};
+    use crate::binary::test_symbol;
+    use goblin::elf::header::{EM_386, EM_AARCH64, EM_X86_64};
+    use goblin::elf::reloc::{
+        R_386_32, R_386_PC32, R_AARCH64_ABS64, R_AARCH64_ADR_PREL_PG_HI21, R_X86_64_64,
+        R_X86_64_PLT32, R_X86_64_REX_GOTPCRELX,
+    };
+
+    #[test]
+    fn crc32_of_empty_data() {
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"a"), 0xE8B7_BE43);
+    }
+
+    #[test]
+    fn crc32_of_debug_file_contents() {
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+        assert_eq!(crc32(&[0u8; 32]), 0x190A_55AD);
+    }
+
+    #[test]
+    fn plt_stub_got_slot_amd64() {
+        // jmp qword ptr [rip + 0x2fca]
+        let stub = [
+            0xFF, 0x25, 0xCA, 0x2F, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AMD64, &stub, 0x1030, 0x3FE8),
+            Some(0x4000)
+        );
+
+        // endbr64; bnd jmp qword ptr [rip + 0x2fa5]
+        let stub = [
+            0xF3, 0x0F, 0x1E, 0xFA, 0xF2, 0xFF, 0x25, 0xA5, 0x2F, 0x00, 0x00,
+        ];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AMD64, &stub, 0x1050, 0x3FE8),
+            Some(0x4000)
+        );
+
+        // The PLT header pushes the link map first.
+        let header = [0xFF, 0x35, 0xE2, 0x2F, 0x00, 0x00];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AMD64, &header, 0x1020, 0x3FE8),
+            None
+        );
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AMD64, &[0xFF, 0x25, 0xCA], 0x1030, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn plt_stub_got_slot_x86() {
+        // jmp dword ptr [0x804a00c]
+        let stub = [0xFF, 0x25, 0x0C, 0xA0, 0x04, 0x08];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::X86, &stub, 0x8049030, 0x804A000),
+            Some(0x804A00C)
+        );
+
+        // jmp dword ptr [ebx + 0xc]
+        let stub = [0xFF, 0xA3, 0x0C, 0x00, 0x00, 0x00];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::X86, &stub, 0x1030, 0x4000),
+            Some(0x400C)
+        );
+    }
+
+    #[test]
+    fn plt_stub_got_slot_aarch64() {
+        // adrp x16, 0x11000; ldr x17, [x16, #16]
+        let stub = [0x90, 0x00, 0x00, 0xB0, 0x11, 0x0A, 0x40, 0xF9];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AArch64, &stub, 0x400_5D0, 0),
+            Some(0x411_010)
+        );
+
+        // bti c; adrp x16, -0x1000; ldr x17, [x16, #16] (the adrp is at 0x3000)
+        let stub = [
+            0x5F, 0x24, 0x03, 0xD5, 0xF0, 0xFF, 0xFF, 0xF0, 0x11, 0x0A, 0x40, 0xF9,
+        ];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AArch64, &stub, 0x2FFC, 0),
+            Some(0x2010)
+        );
+
+        // An adrp to another register isn't the start of a stub.
+        let stub = [0x91, 0x00, 0x00, 0xB0, 0x11, 0x0A, 0x40, 0xF9];
+        assert_eq!(
+            plt_stub_got_slot(BinaryArch::AArch64, &stub, 0x400_5D0, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn pc_relative_relocations() {
+        assert!(is_pc_relative_relocation(EM_X86_64, R_X86_64_PLT32));
+        assert!(is_pc_relative_relocation(EM_X86_64, R_X86_64_REX_GOTPCRELX));
+        assert!(!is_pc_relative_relocation(EM_X86_64, R_X86_64_64));
+        assert!(is_pc_relative_relocation(EM_386, R_386_PC32));
+        assert!(!is_pc_relative_relocation(EM_386, R_386_32));
+        assert!(is_pc_relative_relocation(
+            EM_AARCH64,
+            R_AARCH64_ADR_PREL_PG_HI21
+        ));
+        assert!(!is_pc_relative_relocation(EM_AARCH64, R_AARCH64_ABS64));
+    }
+
+    #[test]
+    fn symbol_ranges_overlap() {
+        let ranges = SymbolRanges::new(&[
+            test_symbol("sym", 0x1000, 0x1000),
+            test_symbol("sym", 0x1100, 0x10),
+            test_symbol("sym", 0x3000, 0),
+            test_symbol("sym", 0x4000, 0x10),
+        ]);
+
+        // Inside of the large symbol, after the end of the small symbol that it contains.
+        assert!(ranges.overlaps(0x1800, 0x1810));
+        assert!(ranges.overlaps(0x0ff0, 0x1001));
+        assert!(ranges.overlaps(0x3000, 0x3010));
+        assert!(ranges.overlaps(0x3ff0, 0x4001));
+        assert!(!ranges.overlaps(0x2000, 0x2010));
+        assert!(!ranges.overlaps(0x3001, 0x4000));
+        assert!(!ranges.overlaps(0x4010, 0x4020));
+        assert!(!SymbolRanges::new(&[]).overlaps(0x1000, 0x1010));
+    }
+}
@@ -1,10 +1,15 @@
 use super::dwarf::DwarfLineMapper;
 use super::{
-    demangle_name, Binary, BinaryArch, BinaryBits, BinaryData, BinaryEndian, FileResolveStrategy,
-    LineMapper, ObjectExt, Symbol,
+    demangle_name, infer_symbol_sizes, Binary, BinaryArch, BinaryBits, BinaryData, BinaryEndian,
+    FileResolveStrategy, LineMapper, ObjectExt, Symbol,
 };
-use goblin::mach::symbols;
-use goblin::mach::{Mach, MachO};
+use crate::errors::CargoAsmError;
+use goblin::mach::constants::cputype;
+use goblin::mach::fat::FatArch;
+use goblin::mach::header;
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::symbols::{self, Nlist};
+use goblin::mach::{Mach, MachO, MultiArch};
 use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 
@@ -12,22 +17,97 @@ pub fn analyze_mach<'a>(
     mach: Mach<'a>,
     data: &'a BinaryData,
     binary_path: &Path,
+    arch_name: Option<&str>,
+    debug_file: Option<&Path>,
     load_debug_info: bool,
 ) -> anyhow::Result<Binary<'a>> {
     match mach {
         goblin::mach::Mach::Fat(multi) => {
-            analyze_mach_object(multi.get(0)?, data, binary_path, load_debug_info)
+            let (index, arch) = select_fat_arch(&multi, arch_name)?;
+            let mut binary = analyze_mach_object(
+                multi.get(index)?,
+                data,
+                binary_path,
+                debug_file,
+                load_debug_info,
+            )?;
+
+            // Offsets in a Mach-O are relative to the start of its slice, not the universal
+            // binary.
+            for symbol in binary.symbols.iter_mut() {
+                symbol.offset += arch.offset as usize;
+            }
+            if let ObjectExt::Mach(ref mut ext) = binary.object {
+                ext.slice_offset = arch.offset as usize;
+            }
+
+            Ok(binary)
         }
         goblin::mach::Mach::Binary(obj) => {
-            analyze_mach_object(obj, data, binary_path, load_debug_info)
+            analyze_mach_object(obj, data, binary_path, debug_file, load_debug_info)
         }
     }
 }
 
+/// Picks the slice of a universal binary that matches the architecture name given with `--arch`
+/// (e.g. `x86_64` or `arm64`). Without one, the first slice is used.
+fn select_fat_arch(multi: &MultiArch, arch_name: Option<&str>) -> anyhow::Result<(usize, FatArch)> {
+    let arches = multi.arches()?;
+
+    let arch_name = if let Some(arch_name) = arch_name {
+        arch_name
+    } else {
+        let first = arches
+            .first()
+            .copied()
+            .ok_or(CargoAsmError::UnsupportedBinaryFormat(
+                "Mach-O universal binary without architectures",
+            ))?;
+        if arches.len() > 1 {
+            eprintln!(
+                "warning: universal binary contains more than one architecture ({}), using {}. Use --arch to choose one.",
+                fat_arch_names(&arches),
+                fat_arch_name(&first),
+            );
+        }
+        return Ok((0, first));
+    };
+
+    let found = cputype::get_arch_from_flag(arch_name).and_then(|(cpu_type, cpu_subtype)| {
+        // An exact match (e.g. `x86_64h` or `arm64e`) is preferred over a slice that only has the
+        // same CPU type.
+        arches
+            .iter()
+            .position(|arch| arch.cputype == cpu_type && arch.cpusubtype() == cpu_subtype)
+            .or_else(|| arches.iter().position(|arch| arch.cputype == cpu_type))
+    });
+
+    if let Some(index) = found {
+        Ok((index, arches[index]))
+    } else {
+        Err(CargoAsmError::NoArchMatch(arch_name.to_string(), fat_arch_names(&arches)).into())
+    }
+}
+
+fn fat_arch_name(arch: &FatArch) -> String {
+    cputype::get_arch_name_from_types(arch.cputype, arch.cpusubtype())
+        .map(String::from)
+        .unwrap_or_else(|| format!("cputype {}:{}", arch.cputype, arch.cpusubtype()))
+}
+
+fn fat_arch_names(arches: &[FatArch]) -> String {
+    arches
+        .iter()
+        .map(fat_arch_name)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 pub fn analyze_mach_object<'a>(
     mach: MachO<'a>,
     data: &'a BinaryData,
     binary_path: &Path,
+    debug_file: Option<&Path>,
     _load_debug_info: bool,
 ) -> anyhow::Result<Binary<'a>> {
     let bits = if mach.is_64 {
@@ -45,69 +125,34 @@ pub fn analyze_mach_object<'a>(
     let arch = BinaryArch::from_mach_cpu_types(mach.header.cputype, mach.header.cpusubtype)
         .expect("[FIXME] unknown mach cpu type");
 
-    let mut section_offsets: Vec<(u64, usize)> = Vec::new();
+    // The address, file offset and size of each section, in the order of their section numbers.
+    let mut sections: Vec<(u64, usize, u64)> = Vec::new();
     for segment in mach.segments.iter() {
         for s in segment.into_iter() {
             let (section, _) = s?;
-            section_offsets.push((section.addr as u64, section.offset as usize));
+            sections.push((section.addr as u64, section.offset as usize, section.size));
         }
     }
 
-    let mut symbols = Vec::new();
-    let mut symbol_addresses: Vec<u64> = Vec::new();
-    let mut symbols_it = mach.symbols();
-    while let Some(Ok((sym_name, sym))) = symbols_it.next() {
-        if sym.n_sect == symbols::NO_SECT as usize || !sym.is_stab() {
-            continue;
-        }
-
-        let sym_addr = sym.n_value;
-        symbol_addresses.push(sym_addr);
-
-        if sym.n_type != MACH_TYPE_FUNC || sym_name.len() < 1 {
-            continue;
-        }
-
-        let sym_offset = if let Some((sec_addr, sec_off)) = section_offsets.get(sym.n_sect - 1) {
-            (sym_addr - sec_addr) as usize + sec_off
-        } else {
-            continue;
-        };
-
-        let sym_name_demangled = demangle_name(sym_name);
+    let symbols = get_stab_symbols(mach.symbols(), &sections);
 
-        symbols.push(Symbol {
-            original_name: Cow::from(sym_name),
-            demangled_name: sym_name_demangled,
-            short_demangled_name: Default::default(),
-
-            addr: sym_addr,
-            offset: sym_offset as usize,
-            size: 0,
-        });
-    }
-
-    symbol_addresses.sort_unstable();
-    symbol_addresses.dedup();
-
-    for symbol in symbols.iter_mut() {
-        if let Ok(idx) = symbol_addresses.binary_search(&symbol.addr) {
-            if let Some(next_addr) = symbol_addresses.get(idx + 1) {
-                symbol.size = (next_addr - symbol.addr) as usize;
-                continue;
-            }
-        };
-        symbol.addr = 0;
-    }
-
-    let ext = if let Some(external_dwarf) = find_external_dwarf(binary_path) {
+    // dSYM bundles are only created for linked binaries, so there is no point in searching for
+    // them for objects, which are usually archive members.
+    let dsyms = if debug_file.is_none() && mach.header.filetype == header::MH_OBJECT {
+        Vec::new()
+    } else {
+        find_external_dwarf(binary_path, debug_file)
+    };
+    let ext = if !dsyms.is_empty() {
         MachExt {
             mach,
-            debug: MachDebug::External(external_dwarf),
+            slice_offset: 0,
+            debug: MachDebug::External(dsyms),
         }
     } else {
         MachExt {
             mach,
+            slice_offset: 0,
             debug: MachDebug::Internal,
         }
     };
@@ -118,10 +163,60 @@ pub fn analyze_mach_object<'a>(
         arch,
         endian,
         symbols,
+        mappings: Vec::new(),
+        local_entries: Vec::new(),
+        relocations: Vec::new(),
+        import_symbols: Vec::new(),
         object: ObjectExt::Mach(ext),
     })
 }
 
+/// Returns a symbol for each function in the `N_FUN` stab entries of a symbol table. These don't
+/// have a size, so it is guessed from the next function or the end of the function's section.
+fn get_stab_symbols<'a, I>(mut symbols_it: I, sections: &[(u64, usize, u64)]) -> Vec<Symbol<'a>>
+where
+    I: Iterator<Item = goblin::error::Result<(&'a str, Nlist)>>,
+{
+    let mut symbols = Vec::new();
+    let mut unsized_symbols = Vec::new();
+    while let Some(Ok((sym_name, sym))) = symbols_it.next() {
+        if sym.n_sect == symbols::NO_SECT as usize || !sym.is_stab() {
+            continue;
+        }
+
+        if sym.n_type != MACH_TYPE_FUNC || sym_name.is_empty() {
+            continue;
+        }
+
+        let sym_addr = sym.n_value;
+        let (sym_offset, section_end) =
+            if let Some(&(sec_addr, sec_off, sec_size)) = sections.get(sym.n_sect - 1) {
+                (
+                    (sym_addr - sec_addr) as usize + sec_off,
+                    sec_addr + sec_size,
+                )
+            } else {
+                continue;
+            };
+
+        unsized_symbols.push((symbols.len(), section_end));
+        symbols.push(Symbol {
+            original_name: Cow::from(sym_name),
+            demangled_name: demangle_name(sym_name),
+            short_demangled_name: Default::default(),
+
+            addr: sym_addr,
+            offset: sym_offset,
+            size: 0,
+            size_inferred: false,
+            archive_member: None,
+        });
+    }
+
+    infer_symbol_sizes(&mut symbols, &unsized_symbols);
+    symbols
+}
+
 pub(super) fn mach_line_mapper<'a>(
     mach: &'a MachExt<'a>,
     endian: BinaryEndian,
@@ -133,8 +228,18 @@ pub(super) fn mach_line_mapper<'a>(
         MachDebug::Internal => {
             mach_internal_line_mapper(&mach.mach, endian, base_directory, resolve_strategy)
         }
-        MachDebug::External(ref dwarf_file) => {
-            mach_external_line_mapper(&binary_data, dwarf_file, base_directory, resolve_strategy)
+        MachDebug::External(ref dsyms) => {
+            if let Some((dwarf_data, slice_index)) = select_external_dwarf(&mach.mach, dsyms) {
+                mach_external_line_mapper(
+                    dwarf_data,
+                    slice_index,
+                    &binary_data,
+                    base_directory,
+                    resolve_strategy,
+                )
+            } else {
+                mach_internal_line_mapper(&mach.mach, endian, base_directory, resolve_strategy)
+            }
         }
     }
 }
@@ -179,14 +284,14 @@ fn mach_internal_line_mapper<'a>(
 }
 
 fn mach_external_line_mapper<'a>(
+    dwarf_data: Vec<u8>,
+    slice_index: usize,
     binary_data: &'a BinaryData,
-    dwarf_path: &Path,
     base_directory: &Path,
     resolve_strategy: FileResolveStrategy,
 ) -> anyhow::Result<Box<dyn 'a + LineMapper>> {
     use goblin::Object;
 
-    let dwarf_data = std::fs::read(dwarf_path)?;
     binary_data.set_dwarf_data(dwarf_data);
 
     let dwarf_data_ref = binary_data.dwarf_data();
@@ -198,7 +303,7 @@ fn mach_external_line_mapper<'a>(
 
     let dwarf_mach = if let Object::Mach(mach) = Object::parse(dwarf_data_slice)? {
         match mach {
-            goblin::mach::Mach::Fat(multi) => multi.get(0)?,
+            goblin::mach::Mach::Fat(multi) => multi.get(slice_index)?,
             goblin::mach::Mach::Binary(obj) => obj,
         }
     } else {
@@ -246,40 +351,182 @@ fn mach_external_line_mapper<'a>(
     Ok(mapper)
 }
 
-// FIXME this is a very bad way to search for DWARF debug information. The path to the separate
-// DWAF binary is actually stored somewhere in the Mach-O file (If you run `strings` on it the path
-// can be found), I just have no idea where exactly in the Mach-O it's stored and I can't find
-// information on it either. I'll just have to dig through all of the load commands at some point
-// to find where the string is placed.
-fn find_external_dwarf(binary_path: &Path) -> Option<PathBuf> {
-    let directory = binary_path.parent()?;
-    let dsym_name = {
-        let mut n = binary_path.file_stem()?.to_os_string();
-        n.push(".dSYM");
-        n
-    };
-    let dsym_path = directory.join(dsym_name);
-    let dwarf_path = {
-        let mut p = dsym_path;
-        p.push("Contents");
-        p.push("Resources");
-        p.push("DWARF");
-        p.push(binary_path.file_name()?);
-        p
+/// Finds dSYM companions that might contain the DWARF debug information for the binary. An
+/// explicit `--debug-file` (either a dSYM bundle or the DWARF file inside of one) comes first,
+/// then bundles named after the binary, then any other bundle in the binary's directory or its
+/// `deps` directory. Which one actually belongs to the binary is decided by comparing UUIDs once
+/// the debug information is needed.
+fn find_external_dwarf(binary_path: &Path, debug_file: Option<&Path>) -> Vec<DsymCandidate> {
+    let mut candidates = Vec::new();
+
+    if let Some(debug_file) = debug_file {
+        if debug_file.is_dir() {
+            push_dsym_dwarf_files(debug_file, true, &mut candidates);
+        } else {
+            candidates.push(DsymCandidate {
+                path: debug_file.to_path_buf(),
+                expected: true,
+            });
+        }
+    }
+
+    let (directory, stem) = match (binary_path.parent(), binary_path.file_stem()) {
+        (Some(directory), Some(stem)) => (directory, stem.to_string_lossy()),
+        _ => return candidates,
     };
+    // Cargo replaces dashes with underscores for the names of files in `deps`.
+    let deps_stem = stem.replace('-', "_");
+
+    let mut dsyms: Vec<(u8, PathBuf)> = Vec::new();
+    for dsym_dir in [directory.to_path_buf(), directory.join("deps")].iter() {
+        let entries = if let Ok(entries) = dsym_dir.read_dir() {
+            entries
+        } else {
+            continue;
+        };
 
-    if dwarf_path.is_file() {
-        Some(dwarf_path)
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().map(|ext| ext != "dSYM").unwrap_or(true) {
+                continue;
+            }
+
+            let dsym_stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let rank = if dsym_stem == stem {
+                0
+            } else if dsym_stem.starts_with(&format!("{}-", deps_stem)) {
+                1
+            } else {
+                2
+            };
+            dsyms.push((rank, path));
+        }
+    }
+    dsyms.sort();
+
+    for (rank, dsym_path) in dsyms {
+        push_dsym_dwarf_files(&dsym_path, rank < 2, &mut candidates);
+    }
+
+    candidates
+}
+
+/// Adds the DWARF files inside of a dSYM bundle (`[name].dSYM/Contents/Resources/DWARF/*`).
+fn push_dsym_dwarf_files(dsym_path: &Path, expected: bool, candidates: &mut Vec<DsymCandidate>) {
+    let dwarf_dir = dsym_path.join("Contents").join("Resources").join("DWARF");
+    let entries = if let Ok(entries) = dwarf_dir.read_dir() {
+        entries
     } else {
-        None
+        return;
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.is_file() {
+            candidates.push(DsymCandidate { path, expected });
+        }
     }
 }
 
+/// Reads the first dSYM candidate with the same UUID as the binary and returns its data along with
+/// the index of the matching slice (which is always 0 if the dSYM isn't a universal binary).
+/// Candidates that don't match are skipped so that source lines from a stale build are never
+/// shown.
+fn select_external_dwarf(
+    binary_mach: &MachO,
+    candidates: &[DsymCandidate],
+) -> Option<(Vec<u8>, usize)> {
+    let uuid = mach_uuid(binary_mach);
+
+    for candidate in candidates.iter() {
+        // Without a UUID to compare, only trust files that are named after the binary.
+        if uuid.is_none() && !candidate.expected {
+            continue;
+        }
+
+        let slice_index = std::fs::read(&candidate.path)
+            .ok()
+            .and_then(|data| Some((dsym_slice_index(binary_mach, uuid, &data)?, data)));
+
+        if let Some((slice_index, data)) = slice_index {
+            return Some((data, slice_index));
+        } else if candidate.expected {
+            eprintln!(
+                "warning: ignoring debug file `{}` because its UUID does not match the binary.",
+                candidate.path.display()
+            );
+        }
+    }
+
+    None
+}
+
+fn dsym_slice_index(binary_mach: &MachO, uuid: Option<[u8; 16]>, data: &[u8]) -> Option<usize> {
+    match goblin::Object::parse(data).ok()? {
+        goblin::Object::Mach(Mach::Binary(dwarf_mach)) => {
+            if uuid.is_none() || mach_uuid(&dwarf_mach) == uuid {
+                Some(0)
+            } else {
+                None
+            }
+        }
+
+        goblin::Object::Mach(Mach::Fat(multi)) => {
+            if uuid.is_some() {
+                return (0..multi.narches)
+                    .find(|&index| multi.get(index).ok().and_then(|m| mach_uuid(&m)) == uuid);
+            }
+
+            // Without a UUID, use the same architecture as the slice that is being disassembled.
+            let arches = multi.arches().ok()?;
+            arches
+                .iter()
+                .position(|arch| {
+                    arch.cputype == binary_mach.header.cputype
+                        && arch.cpusubtype() == binary_mach.header.cpusubtype()
+                })
+                .or_else(|| {
+                    arches
+                        .iter()
+                        .position(|arch| arch.cputype == binary_mach.header.cputype)
+                })
+                .or(Some(0))
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns the UUID from the `LC_UUID` load command. A binary and its dSYM have the same UUID.
+fn mach_uuid(mach: &MachO) -> Option<[u8; 16]> {
+    mach.load_commands
+        .iter()
+        .find_map(|load_command| match load_command.command {
+            CommandVariant::Uuid(ref uuid_command) => Some(uuid_command.uuid),
+            _ => None,
+        })
+}
+
+pub(super) fn mach_addr_to_offset(mach: &MachExt, addr: u64) -> Option<usize> {
+    mach.mach
+        .segments
+        .iter()
+        .find(|segment| addr >= segment.vmaddr && addr < (segment.vmaddr + segment.filesize))
+        .map(|segment| (addr - segment.vmaddr + segment.fileoff) as usize + mach.slice_offset)
+}
+
 const MACH_TYPE_FUNC: u8 = 0x24;
 
 #[derive(Debug)]
 pub struct MachExt<'a> {
     mach: MachO<'a>,
+
+    /// Offset of the Mach-O in the file when it is a slice of a universal binary.
+    slice_offset: usize,
+
     debug: MachDebug,
 }
 
@@ -288,8 +535,18 @@ pub enum MachDebug {
     /// DWARF debug information is in the Mach-O itself.
     Internal,
 
-    /// DWARF debug information is in some separate binary (usually in [binary].dSYM somewhere)
-    External(PathBuf),
+    /// DWARF debug information might be in one of these separate binaries (usually in
+    /// [binary].dSYM somewhere).
+    External(Vec<DsymCandidate>),
+}
+
+#[derive(Debug)]
+pub struct DsymCandidate {
+    path: PathBuf,
+
+    /// Whether the file was passed with `--debug-file` or is named after the binary, in which case
+    /// a warning is printed when its UUID doesn't match.
+    expected: bool,
 }
 
 fn get_section_by_name<'a>(mach: &MachO<'a>, mut name: &str) -> anyhow::Result<&'a [u8]> {
@@ -321,3 +578,43 @@ fn fix_section_name(name: &str) -> &str {
         name
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get_stab_symbols, Nlist, MACH_TYPE_FUNC};
+    use crate::binary::test_symbol_layout;
+
+    const N_STSYM: u8 = 0x26;
+
+    fn stab(n_type: u8, n_sect: usize, n_value: u64) -> Nlist {
+        Nlist {
+            n_strx: 0,
+            n_type,
+            n_sect,
+            n_desc: 0,
+            n_value,
+        }
+    }
+
+    #[test]
+    fn stab_symbols_end_at_next_function_or_section_end() {
+        let sections = [(0x1000, 0x4000, 0x100), (0x2000, 0x5000, 0x10)];
+        let stabs = vec![
+            Ok(("_a", stab(MACH_TYPE_FUNC, 1, 0x1000))),
+            // The end of a function is marked by an unnamed entry that holds its size.
+            Ok(("", stab(MACH_TYPE_FUNC, 0, 0x40))),
+            Ok(("_b", stab(MACH_TYPE_FUNC, 1, 0x1040))),
+            Ok(("", stab(MACH_TYPE_FUNC, 0, 0x10))),
+            Ok(("_data", stab(N_STSYM, 2, 0x2000))),
+        ];
+
+        let symbols = get_stab_symbols(stabs.into_iter(), &sections);
+        assert_eq!(
+            test_symbol_layout(&symbols),
+            [
+                ("_a", 0x1000, 0x4000, 0x40, true),
+                ("_b", 0x1040, 0x4040, 0xC0, true)
+            ]
+        );
+    }
+}
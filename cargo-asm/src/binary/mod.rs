@@ -1,15 +1,19 @@
+pub mod archive;
 mod arena;
 pub mod dwarf;
 pub mod elf;
 pub mod mach;
 pub mod pdb_lines;
 pub mod pe;
+pub mod split_dwarf;
 
 use crate::errors::CargoAsmError;
 use crate::platform::{path_converter_from, NativePathConverter, PathConverter, Platform};
 use arena::StringArena;
 use goblin::Object;
+use once_cell::sync::Lazy;
 use once_cell::unsync::OnceCell;
+use regex::Regex;
 use std::borrow::Cow;
 use std::cell::{Ref, RefCell, RefMut};
 use std::ops::Range;
@@ -22,6 +26,18 @@ pub struct BinaryData {
     data: Box<[u8]>,
     syms: RefCell<StringArena<'static>>,
     debug: RefCell<Option<DebugInfo<'static>>>,
+
+    /// Data of a supplementary DWARF file (see `.gnu_debugaltlink`) that the debug information
+    /// refers to.
+    sup_dwarf: RefCell<Option<Vec<u8>>>,
+
+    /// Debug sections that had to be relocated or decompressed.
+    sections: RefCell<Vec<Box<[u8]>>>,
+
+    /// Data of the objects inside of an archive, which are analyzed as binaries of their own.
+    /// They are boxed so references to them stay valid when the vector grows.
+    #[allow(clippy::vec_box)]
+    members: RefCell<Vec<Box<BinaryData>>>,
 }
 
 impl BinaryData {
@@ -30,6 +46,9 @@ impl BinaryData {
             data: data.into_boxed_slice(),
             syms: RefCell::new(StringArena::new()),
             debug: RefCell::new(None),
+            sup_dwarf: RefCell::new(None),
+            sections: RefCell::new(Vec::new()),
+            members: RefCell::new(Vec::new()),
         }
     }
 
@@ -67,6 +86,20 @@ impl BinaryData {
         }
     }
 
+    fn sup_dwarf_data<'a>(&'a self) -> Ref<'a, [u8]> {
+        // Casting away lifetimes :)
+        unsafe {
+            std::mem::transmute::<Ref<'_, [u8]>, Ref<'a, [u8]>>(Ref::map(
+                self.sup_dwarf.borrow(),
+                |d| {
+                    d.as_ref()
+                        .map(|data| data as &[u8])
+                        .expect("no supplementary dwarf data")
+                },
+            ))
+        }
+    }
+
     fn pdb_mut<'a>(&'a self) -> RefMut<'a, FilePDB<'a>> {
         // Casting away lifetimes :)
         // I don't move any of the data in this struct if that makes you feel better.
@@ -89,6 +122,32 @@ impl BinaryData {
         *self.debug.borrow_mut() = Some(DebugInfo::DwarfData(data));
     }
 
+    fn set_sup_dwarf_data(&self, data: Vec<u8>) {
+        assert!(
+            self.sup_dwarf.borrow().is_none(),
+            "cannot reassign supplementary dwarf data"
+        );
+        *self.sup_dwarf.borrow_mut() = Some(data);
+    }
+
+    fn store_section<'a>(&'a self, data: Vec<u8>) -> &'a [u8] {
+        let data = data.into_boxed_slice();
+        // Casting away lifetimes :)
+        // The boxed data doesn't move when the vector that owns it does and it is never removed.
+        let section = unsafe { std::mem::transmute::<&'_ [u8], &'a [u8]>(&*data) };
+        self.sections.borrow_mut().push(data);
+        section
+    }
+
+    fn store_member<'a>(&'a self, data: &[u8]) -> &'a BinaryData {
+        let member = Box::new(BinaryData::load(data.to_vec()));
+        // Casting away lifetimes :)
+        // The boxed member doesn't move when the vector that owns it does and it is never removed.
+        let member_ref = unsafe { std::mem::transmute::<&'_ BinaryData, &'a BinaryData>(&*member) };
+        self.members.borrow_mut().push(member);
+        member_ref
+    }
+
     fn set_pdb(&self, pdb: FilePDB<'static>) {
         assert!(self.debug.borrow().is_none(), "cannot reassign debug info");
         *self.debug.borrow_mut() = Some(DebugInfo::PDB(pdb));
@@ -108,6 +167,27 @@ pub struct Binary<'a> {
     pub bits: BinaryBits,
     pub endian: BinaryEndian,
     pub symbols: Vec<Symbol<'a>>,
+
+    /// Addresses where the kind of bytes found in code sections changes, sorted by address. This
+    /// is only filled in for ARM binaries which mix ARM code, Thumb code and literal pools.
+    pub mappings: Vec<(u64, MappingKind)>,
+
+    /// Pairs of local and global entry point addresses for PPC64 ELFv2 functions that have a
+    /// separate local entry point, sorted by the local entry point. Calls from within the same
+    /// module skip the TOC pointer setup at the global entry point (the symbol's address) and
+    /// branch to the local one instead.
+    pub local_entries: Vec<(u64, u64)>,
+
+    /// Relocations that apply to the code of a relocatable object file, sorted by address. This
+    /// is empty for executables and shared libraries.
+    pub relocations: Vec<Relocation<'a>>,
+
+    /// Symbols for the slots that are filled in with the addresses of imported symbols by the
+    /// dynamic linker (the GOT of an ELF or the import address table of a PE), sorted by the
+    /// address of the slot. Indirect calls through one of these slots are displayed as calls to
+    /// `foo@GOT` or `KERNEL32!CreateFileW`.
+    pub import_symbols: Vec<Symbol<'a>>,
+
     pub object: ObjectExt<'a>,
 }
 
@@ -115,21 +195,31 @@ impl<'a> Binary<'a> {
     pub fn load(
         data: &'a BinaryData,
         binary_path: &Path,
+        arch_name: Option<&str>,
+        debug_file: Option<&Path>,
         debug_info: bool,
     ) -> anyhow::Result<Binary<'a>> {
         match Object::parse(data.data())? {
-            Object::Elf(elf) => elf::analyze_elf(elf, data, debug_info),
+            Object::Elf(elf) => elf::analyze_elf(elf, data, binary_path, debug_file, debug_info),
 
-            Object::PE(pe) => pe::analyze_pe(pe, data, binary_path, debug_info),
+            Object::PE(pe) => pe::analyze_pe(pe, data, binary_path, debug_file, debug_info),
 
-            Object::Mach(mach) => mach::analyze_mach(mach, data, binary_path, debug_info),
+            Object::Mach(mach) => {
+                mach::analyze_mach(mach, data, binary_path, arch_name, debug_file, debug_info)
+            }
 
-            Object::Archive(_archive) => {
-                Err(CargoAsmError::UnsupportedBinaryFormat("Archive").into())
+            Object::Archive(archive) => {
+                archive::analyze_archive(archive, data, binary_path, debug_info)
             }
 
             Object::Unknown(_unknown) => {
-                Err(CargoAsmError::UnsupportedBinaryFormat("<< UNKNOWN >>").into())
+                // COFF object files don't start with a magic number so goblin doesn't recognize
+                // them.
+                if let Some(coff) = pe::parse_coff_object(data.data()) {
+                    pe::analyze_coff(coff, data)
+                } else {
+                    Err(CargoAsmError::UnsupportedBinaryFormat("<< UNKNOWN >>").into())
+                }
             }
         }
     }
@@ -149,7 +239,7 @@ impl<'a> Binary<'a> {
                 mapper = elf::elf_line_mapper(
                     elf,
                     self.endian,
-                    &self.data.data(),
+                    &self.data,
                     base_directory,
                     resolve_strategy,
                 )?;
@@ -178,6 +268,24 @@ impl<'a> Binary<'a> {
                 )?;
                 convert_path = path_converter_from(Platform::Unix);
             }
+
+            // FIXME the debug information in object files is only correct once relocations have
+            //       been applied to it.
+            ObjectExt::Coff(_) => {
+                return Err(CargoAsmError::UnsupportedBinaryFormatOp(
+                    "COFF Object",
+                    "source line mapping",
+                )
+                .into())
+            }
+
+            ObjectExt::Archive(_) => {
+                return Err(CargoAsmError::UnsupportedBinaryFormatOp(
+                    "Archive",
+                    "source line mapping",
+                )
+                .into())
+            }
         };
 
         Ok(LineMappings::new(mapper, convert_path))
@@ -186,13 +294,102 @@ impl<'a> Binary<'a> {
     pub fn data(&self) -> &[u8] {
         self.data.data()
     }
+
+    /// Returns the file offset of a virtual address if that address is backed by data in the
+    /// binary file.
+    pub fn addr_to_offset(&self, addr: u64) -> Option<usize> {
+        match &self.object {
+            ObjectExt::Elf(ref elf) => elf::elf_addr_to_offset(elf, addr),
+            ObjectExt::PE(ref pe) => pe::pe_addr_to_offset(pe, addr),
+            ObjectExt::Mach(ref mach) => mach::mach_addr_to_offset(mach, addr),
+            ObjectExt::Coff(ref coff) => pe::coff_addr_to_offset(coff, addr),
+            ObjectExt::Archive(ref members) => archive::archive_addr_to_offset(members, addr),
+        }
+    }
+
+    /// Returns the relocations that patch bytes in the given address range.
+    pub fn relocations_in(&self, range: Range<u64>) -> &[Relocation<'a>] {
+        let start = self
+            .relocations
+            .binary_search_by(|r| r.addr.cmp(&range.start).then(std::cmp::Ordering::Greater))
+            .unwrap_or_else(|idx| idx);
+        let end = self.relocations[start..]
+            .iter()
+            .take_while(|r| r.addr < range.end)
+            .count();
+        &self.relocations[start..(start + end)]
+    }
+
+    /// Returns the symbol for the import slot at `addr`.
+    pub fn import_symbol(&self, addr: u64) -> Option<&Symbol<'a>> {
+        self.import_symbols
+            .binary_search_by_key(&addr, |sym| sym.addr)
+            .ok()
+            .map(|idx| &self.import_symbols[idx])
+    }
+
+    /// Creates a symbol for disassembling a range of addresses that isn't necessarily covered by a
+    /// symbol. Returns `None` if the range isn't backed by contiguous data in the binary file.
+    pub fn range_symbol(&self, range: Range<u64>) -> Option<Symbol<'a>> {
+        if range.start >= range.end {
+            return None;
+        }
+
+        let size = (range.end - range.start) as usize;
+        let offset = self.addr_to_offset(range.start)?;
+        if self.addr_to_offset(range.end - 1)? != offset + size - 1
+            || offset + size > self.data().len()
+        {
+            return None;
+        }
+
+        let name = format!("{:#x}..{:#x}", range.start, range.end);
+        Some(Symbol {
+            original_name: Cow::from(name.clone()),
+            demangled_name: Cow::from(name),
+            short_demangled_name: Default::default(),
+
+            addr: range.start,
+            offset,
+            size,
+            size_inferred: false,
+            archive_member: None,
+        })
+    }
+
+    /// Returns `len` bytes of data starting at a virtual address in the binary.
+    pub fn data_at_addr(&self, addr: u64, len: usize) -> Option<&[u8]> {
+        let offset = self.addr_to_offset(addr)?;
+        self.data().get(offset..(offset + len))
+    }
+}
+
+/// A relocation that the linker would apply to the code in a relocatable object file.
+#[derive(Debug)]
+pub struct Relocation<'a> {
+    /// Address of the bytes that are patched by the relocation.
+    pub addr: u64,
+
+    /// Name of the relocation type, e.g. `R_X86_64_PLT32`.
+    pub kind: Cow<'static, str>,
+
+    /// True if the relocation writes an address relative to `addr` instead of an absolute one.
+    pub pc_relative: bool,
+
+    /// Demangled name of the symbol that the relocation refers to. This is the name of the
+    /// section for relocations that are relative to a section.
+    pub symbol: Cow<'a, str>,
+
+    pub addend: i64,
 }
 
 #[derive(Debug)]
 pub enum ObjectExt<'a> {
-    Elf(goblin::elf::Elf<'a>),
+    Elf(elf::ElfExt<'a>),
     PE(pe::PEExt<'a>),
     Mach(mach::MachExt<'a>),
+    Coff(pe::CoffExt<'a>),
+    Archive(Vec<archive::ArchiveMember<'a>>),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -265,14 +462,13 @@ impl BinaryArch {
                     Some(BinaryArch::PowerPC64)
                 }
             }
-            header::COFF_MACHINE_ARM => Some(BinaryArch::ARM),
+            header::COFF_MACHINE_ARM | header::COFF_MACHINE_ARMNT => Some(BinaryArch::ARM),
             header::COFF_MACHINE_ARM64 => Some(BinaryArch::AArch64),
             header::COFF_MACHINE_RISCV32
             | header::COFF_MACHINE_RISCV64
             | header::COFF_MACHINE_RISCV128 => Some(BinaryArch::RiscV),
 
-            // FIXME this is wrong, I should introduce something separate for THUMB mode. I will
-            // probably forget for a while.
+            // Thumb code is marked using `Binary::mappings` when the symbols are loaded.
             header::COFF_MACHINE_THUMB => Some(BinaryArch::ARM),
 
             _ => Some(BinaryArch::Unknown),
@@ -297,6 +493,19 @@ impl BinaryArch {
     }
 }
 
+/// The kind of bytes that follow a mapping symbol (`$a`, `$t` or `$d` in ARM ELF files).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MappingKind {
+    /// Instructions in the architecture's default mode (ARM state for `$a`).
+    Code,
+
+    /// Thumb instructions.
+    Thumb,
+
+    /// Data such as literal pools, which should not be decoded as instructions.
+    Data,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BinaryBits {
     Bits32,
@@ -315,10 +524,101 @@ impl BinaryBits {
     }
 }
 
+/// Symbols without a size are assumed to end where the next symbol starts or at the end of their
+/// section, which is passed along with the index of each unsized symbol. Unsized symbols that end
+/// up with a size of 0 are removed, other symbols are left alone.
+pub(super) fn infer_symbol_sizes(symbols: &mut Vec<Symbol>, unsized_symbols: &[(usize, u64)]) {
+    if unsized_symbols.is_empty() {
+        return;
+    }
+
+    let mut starts: Vec<u64> = symbols.iter().map(|sym| sym.addr).collect();
+    starts.sort_unstable();
+
+    for &(idx, section_end) in unsized_symbols.iter() {
+        let symbol = &mut symbols[idx];
+        let next_idx = starts
+            .binary_search_by(|start| start.cmp(&symbol.addr).then(std::cmp::Ordering::Less))
+            .unwrap_or_else(|idx| idx);
+        let end = starts
+            .get(next_idx)
+            .map(|&next| std::cmp::min(next, section_end))
+            .unwrap_or(section_end);
+
+        symbol.size = end.saturating_sub(symbol.addr) as usize;
+        symbol.size_inferred = true;
+    }
+
+    let mut empty_symbols: Vec<usize> = unsized_symbols
+        .iter()
+        .map(|&(idx, _)| idx)
+        .filter(|&idx| symbols[idx].size == 0)
+        .collect();
+    empty_symbols.sort_unstable();
+
+    let mut idx = 0;
+    symbols.retain(|_| {
+        let keep = empty_symbols.binary_search(&idx).is_err();
+        idx += 1;
+        keep
+    });
+}
+
+/// Creates a symbol for tests whose file offset is the same as its address.
+#[cfg(test)]
+pub(super) fn test_symbol(name: &'static str, addr: u64, size: usize) -> Symbol<'static> {
+    Symbol {
+        original_name: Cow::from(name),
+        demangled_name: Cow::from(name),
+        short_demangled_name: Default::default(),
+
+        addr,
+        offset: addr as usize,
+        size,
+        size_inferred: false,
+        archive_member: None,
+    }
+}
+
+/// Returns the name, address, offset, size and whether the size was inferred of each symbol so
+/// that tests can compare them.
+#[cfg(test)]
+pub(super) fn test_symbol_layout<'s>(
+    symbols: &'s [Symbol],
+) -> Vec<(&'s str, u64, usize, usize, bool)> {
+    symbols
+        .iter()
+        .map(|sym| {
+            (
+                &*sym.original_name,
+                sym.addr,
+                sym.offset,
+                sym.size,
+                sym.size_inferred,
+            )
+        })
+        .collect()
+}
+
 fn demangle_name(name: &str) -> Cow<'_, str> {
+    demangle_name_with_hashes(name, false)
+}
+
+/// Demangles a symbol name. If `hashes` is true the `::h<hash>` suffix of legacy Rust symbols and
+/// the `[<hash>]` crate disambiguators of v0 Rust symbols are kept.
+fn demangle_name_with_hashes(name: &str, hashes: bool) -> Cow<'_, str> {
     if let Ok(demangled) = rustc_demangle::try_demangle(&name) {
-        let demangled_string = format!("{:#}", demangled);
-        Cow::from(demangled_string)
+        let demangled_string = if hashes {
+            format!("{}", demangled)
+        } else {
+            format!("{:#}", demangled)
+        };
+
+        if is_v0_mangled(name) {
+            Cow::from(normalize_v0_name(&demangled_string))
+        } else {
+            Cow::from(demangled_string)
+        }
     } else if let Ok(demangled) = cpp_demangle::Symbol::new(name) {
         Cow::from(demangled.to_string())
     } else {
@@ -326,6 +626,109 @@ fn demangle_name(name: &str) -> Cow<'_, str> {
     }
 }
 
+/// Legacy Rust symbols are mangled like C++ symbols (`_ZN...`), v0 Rust symbols start with `_R`.
+fn is_v0_mangled(name: &str) -> bool {
+    !name.trim_start_matches('_').starts_with("ZN")
+}
+
+/// Rewrites a demangled v0 Rust symbol name in the style of a legacy one so that symbols mangled
+/// with either scheme are matched and displayed the same way. e.g.
+///     <alloc::vec::Vec<u8>>::push::<u8>::{closure#0}
+/// becomes
+///     alloc::vec::Vec<u8>::push<u8>::{{closure}}
+fn normalize_v0_name(name: &str) -> String {
+    static SPECIAL_NAMESPACE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\{(shim:)?([A-Za-z_]+)#\d+\}").unwrap());
+
+    let name = name.replace("::<", "<");
+
+    // v0 wraps the type of inherent impls in angle brackets (`<Type>::method`), which legacy
+    // symbols don't do. A `<` that doesn't follow a path starts one of those, but it might also
+    // be a `<Type as Trait>` which is kept.
+    let mut removed = Vec::new();
+    let mut open: Vec<(
+        /* index */ usize,
+        /* qualified path */ bool,
+        /* has as */ bool,
+    )> = Vec::new();
+    let mut prev = None;
+    for (idx, ch) in name.char_indices() {
+        match ch {
+            '<' => {
+                let follows_path = prev
+                    .map(|p: char| p.is_alphanumeric() || p == '_' || p == ':' || p == '>')
+                    .unwrap_or(false);
+                open.push((idx, !follows_path, false));
+            }
+
+            // Skip the arrow of function pointer types.
+            '>' if prev != Some('-') => {
+                if let Some((start, qualified, has_as)) = open.pop() {
+                    if qualified && !has_as && name[idx + 1..].starts_with("::") {
+                        removed.push(start);
+                        removed.push(idx);
+                    }
+                }
+            }
+
+            ' ' if name[idx..].starts_with(" as ") => {
+                if let Some(last) = open.last_mut() {
+                    last.2 = true;
+                }
+            }
+
+            _ => {}
+        }
+        prev = Some(ch);
+    }
+
+    let name: String = name
+        .char_indices()
+        .filter(|(idx, _)| !removed.contains(idx))
+        .map(|(_, ch)| ch)
+        .collect();
+
+    // `{closure#0}` and `{shim:vtable#0}` are `{{closure}}` and `{{vtable.shim}}` in legacy symbols.
+    SPECIAL_NAMESPACE
+        .replace_all(&name, |caps: &regex::Captures| {
+            if caps.get(1).is_some() {
+                format!("{{{{{}.shim}}}}", &caps[2])
+            } else {
+                format!("{{{{{}}}}}", &caps[2])
+            }
+        })
+        .into_owned()
+}
+
+/// The symbols of a binary sorted by address, for finding the symbol that contains an address.
+pub struct SymbolAddressIndex<'s, 'a> {
+    symbols: Vec<&'s Symbol<'a>>,
+}
+
+impl<'s, 'a> SymbolAddressIndex<'s, 'a> {
+    pub fn new(symbols: &'s [Symbol<'a>]) -> SymbolAddressIndex<'s, 'a> {
+        let mut symbols: Vec<&Symbol> = symbols.iter().filter(|sym| sym.size > 0).collect();
+        symbols.sort_by_key(|sym| sym.addr);
+        SymbolAddressIndex { symbols }
+    }
+
+    /// Returns the symbol that contains `addr`. If several symbols contain it (e.g. a label
+    /// inside of a function) the one that starts closest to `addr` is returned.
+    pub fn containing(&self, addr: u64) -> Option<&'s Symbol<'a>> {
+        // Index of the first symbol that starts after `addr`.
+        let end = self
+            .symbols
+            .binary_search_by(|sym| sym.addr.cmp(&addr).then(std::cmp::Ordering::Less))
+            .unwrap_or_else(|idx| idx);
+
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .find(|sym| addr < sym.addr + sym.size as u64)
+            .copied()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Symbol<'a> {
     /// Original possibly mangled name.
@@ -345,6 +748,14 @@ pub struct Symbol<'a> {
 
     /// The size of teh symbol's data or code in bytes.
     pub size: usize,
+
+    /// True if the binary doesn't record the size of the symbol and it was guessed from where the
+    /// next symbol starts or where the symbol's section ends instead.
+    pub size_inferred: bool,
+
+    /// The name of the archive member (object file) that the symbol was found in if the binary is
+    /// a static library.
+    pub archive_member: Option<String>,
 }
 
 impl<'a> Symbol<'a> {
@@ -358,29 +769,45 @@ impl<'a> Symbol<'a> {
 
     /// Returns a shorter version of a symbols name (removes trait information)
     pub fn short_demangled_name(&self) -> &str {
-        self.short_demangled_name.get_or_init(|| {
-            let mut short_name = String::new();
-
-            for (is_impl, frag) in
-                rust_symbol_fragments(&self.demangled_name).map(rust_impl_fragment)
-            {
-                if is_impl {
-                    // We simplify all of the impl conversion stuff and just use the impl type
-                    // as the root type. e.g.
-                    //      anyhow::context::<impl anyhow::Context<T,E> for core::result::Result<T,E>>::with_context
-                    // becomes
-                    //      anyhow::Context::with_context
-                    short_name.clear();
-                }
+        self.short_demangled_name
+            .get_or_init(|| short_name(&self.demangled_name))
+    }
 
-                if !short_name.is_empty() {
-                    short_name.push_str("::");
-                }
-                short_name.push_str(&frag);
-            }
+    /// Returns the demangled name of the symbol, with the hashes of Rust symbol names (see
+    /// `demangle_name_with_hashes`) if `hashes` is true.
+    pub fn display_name(&self, hashes: bool) -> Cow<'_, str> {
+        if !hashes {
+            return Cow::from(&*self.demangled_name);
+        }
 
-            short_name
-        })
+        // The demangled name might have been decorated (e.g. `foo@plt`), so only the part that
+        // came from demangling the original name is replaced.
+        let plain = demangle_name(&self.original_name);
+        let hashed = demangle_name_with_hashes(&self.original_name, true);
+        if plain == hashed {
+            Cow::from(&*self.demangled_name)
+        } else {
+            Cow::from(self.demangled_name.replacen(&*plain, &hashed, 1))
+        }
+    }
+
+    /// Same as `display_name` but prefixed with the archive member that the symbol was found in
+    /// (e.g. `foo.o: foo::bar`). This is only used for output since the prefix isn't part of the
+    /// symbol's name.
+    pub fn display_name_with_member(&self, hashes: bool) -> Cow<'_, str> {
+        match self.archive_member {
+            Some(ref member) => Cow::from(format!("{}: {}", member, self.display_name(hashes))),
+            None => self.display_name(hashes),
+        }
+    }
+
+    /// Same as `display_name` but for the short demangled name.
+    pub fn display_short_name(&self, hashes: bool) -> Cow<'_, str> {
+        if hashes {
+            Cow::from(short_name(&self.display_name(true)))
+        } else {
+            Cow::from(self.short_demangled_name())
+        }
     }
 }
 
@@ -427,6 +854,62 @@ impl<'a> LineMappings<'a> {
         self.mapper
             .map_address_to_line(address, self.convert_path.as_ref())
     }
+
+    /// Returns the name of the function that the code at `address` was inlined from.
+    pub fn inlined_function(&mut self, address: u64) -> anyhow::Result<Option<&str>> {
+        self.mapper
+            .map_address_to_inlined_function(address, self.convert_path.as_ref())
+    }
+
+    /// Returns the address ranges of the code that was generated for `line` of the source file
+    /// `file`, sorted by address. Adjacent ranges are merged.
+    pub fn addresses_for_line(
+        &mut self,
+        file: &Path,
+        line: u32,
+    ) -> anyhow::Result<Vec<Range<u64>>> {
+        let mut ranges =
+            self.mapper
+                .map_line_to_addresses(file, line, self.convert_path.as_ref())?;
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => {
+                    last.end = std::cmp::max(last.end, range.end)
+                }
+                _ => merged.push(range),
+            }
+        }
+        Ok(merged)
+    }
+}
+
+/// Returns true if `file` names the source file at `path`, which is the case if `path` ends with
+/// the components of `file` (e.g. `src/parser.rs` names `/home/me/project/src/parser.rs`). Paths
+/// from Windows debug information are compared the same way.
+fn source_path_matches(path: &Path, file: &Path, base_directory: &Path) -> bool {
+    // Relative paths from the debug information are opened relative to the source base directory,
+    // so they are compared that way too in case `file` is absolute.
+    let path = if path.is_relative() && file.is_absolute() {
+        Cow::from(base_directory.join(path))
+    } else {
+        Cow::from(path)
+    };
+
+    let path = path.to_string_lossy().replace('\\', "/");
+    let file = file.to_string_lossy().replace('\\', "/");
+    let components = |p: &str| {
+        Path::new(p)
+            .components()
+            .filter(|c| *c != std::path::Component::CurDir)
+            .map(|c| c.as_os_str().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let file = components(&file);
+    !file.is_empty() && components(&path).ends_with(&file)
 }
 
 impl<'a> std::fmt::Debug for LineMappings<'a> {
@@ -441,6 +924,27 @@ trait LineMapper {
         address: u64,
         convert_path: &dyn PathConverter,
     ) -> anyhow::Result<Option<(&Path, u32)>>;
+
+    /// Returns the name of the function that the code at `address` was inlined from, if the
+    /// debug information has inline sites.
+    fn map_address_to_inlined_function(
+        &mut self,
+        _address: u64,
+        _convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Option<&str>> {
+        Ok(None)
+    }
+
+    /// Returns the address ranges of the code that the line table attributes to `line` of the
+    /// source file `file`.
+    fn map_line_to_addresses(
+        &mut self,
+        _file: &Path,
+        _line: u32,
+        _convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Vec<Range<u64>>> {
+        Ok(Vec::new())
+    }
 }
 
 struct NoOpLineMapper;
@@ -455,6 +959,29 @@ impl LineMapper for NoOpLineMapper {
     }
 }
 
+/// Shortens a demangled Rust symbol name by using the type of an impl as the root of the path.
+fn short_name(demangled_name: &str) -> String {
+    let mut short_name = String::new();
+
+    for (is_impl, frag) in rust_symbol_fragments(demangled_name).map(rust_impl_fragment) {
+        if is_impl {
+            // We simplify all of the impl conversion stuff and just use the impl type
+            // as the root type. e.g.
+            //      anyhow::context::<impl anyhow::Context<T,E> for core::result::Result<T,E>>::with_context
+            // becomes
+            //      anyhow::Context::with_context
+            short_name.clear();
+        }
+
+        if !short_name.is_empty() {
+            short_name.push_str("::");
+        }
+        short_name.push_str(&frag);
+    }
+
+    short_name
+}
+
 fn rust_impl_fragment(impl_str: &str) -> (/* is_impl */ bool, &'_ str) {
     let impl_start_index = if let Some(index) = impl_str.find("impl ") {
         index + 5
@@ -525,3 +1052,136 @@ impl<'s> Iterator for RustSymFragmentIter<'s> {
         Some(ret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes<'s>(symbols: &'s [Symbol]) -> Vec<(&'s str, usize, bool)> {
+        symbols
+            .iter()
+            .map(|sym| (&*sym.original_name, sym.size, sym.size_inferred))
+            .collect()
+    }
+
+    #[test]
+    fn infer_sizes_without_unsized_symbols() {
+        let mut symbols = vec![test_symbol("a", 0x10, 0x10), test_symbol("b", 0x20, 0)];
+        infer_symbol_sizes(&mut symbols, &[]);
+        assert_eq!(sizes(&symbols), [("a", 0x10, false), ("b", 0, false)]);
+    }
+
+    #[test]
+    fn infer_sizes_from_next_symbol_and_section_end() {
+        let mut symbols = vec![
+            test_symbol("c", 0x30, 0),
+            test_symbol("a", 0x10, 0),
+            test_symbol("b", 0x20, 0x8),
+        ];
+        infer_symbol_sizes(&mut symbols, &[(0, 0x38), (1, 0x40)]);
+        assert_eq!(
+            sizes(&symbols),
+            [("c", 0x8, true), ("a", 0x10, true), ("b", 0x8, false)]
+        );
+    }
+
+    #[test]
+    fn infer_sizes_skips_aliases() {
+        // Symbols at the same address must not end each other.
+        let mut symbols = vec![test_symbol("a", 0x10, 0), test_symbol("alias", 0x10, 0x4)];
+        infer_symbol_sizes(&mut symbols, &[(0, 0x20)]);
+        assert_eq!(sizes(&symbols), [("a", 0x10, true), ("alias", 0x4, false)]);
+    }
+
+    #[test]
+    fn infer_sizes_removes_empty_symbols() {
+        let mut symbols = vec![test_symbol("a", 0x10, 0), test_symbol("end", 0x20, 0)];
+        infer_symbol_sizes(&mut symbols, &[(0, 0x20), (1, 0x20)]);
+        assert_eq!(sizes(&symbols), [("a", 0x10, true)]);
+    }
+
+    #[test]
+    fn infer_sizes_keeps_other_empty_symbols() {
+        // Symbols that were already in the list with a size of 0 (like the COFF symbols of a DLL
+        // whose exports are added later) must not be removed.
+        let mut symbols = vec![
+            test_symbol("coff", 0x10, 0),
+            test_symbol("export", 0x20, 0),
+            test_symbol("end", 0x30, 0),
+        ];
+        infer_symbol_sizes(&mut symbols, &[(1, 0x30), (2, 0x30)]);
+        assert_eq!(
+            sizes(&symbols),
+            [("coff", 0, false), ("export", 0x10, true)]
+        );
+    }
+
+    #[test]
+    fn demangle_v0_names_with_backrefs() {
+        let names = [
+            (
+                "_RNvMCs5twywQkEBMf_1vINtB2_7WrapperIBj_INtNtCslNYArtu3iFV_5alloc3vec3VechEEE3getB2_",
+                "v::Wrapper<v::Wrapper<alloc::vec::Vec<u8>>>::get",
+            ),
+            (
+                "_RNvXs_Cs5twywQkEBMf_1vINtB4_7WrapperIBl_hEENtB4_2Tr2go",
+                "<v::Wrapper<v::Wrapper<u8>> as v::Tr>::go",
+            ),
+            ("_RNCNvCs5twywQkEBMf_1v3run0B3_", "v::run::{{closure}}"),
+        ];
+        for (mangled, demangled) in names.iter() {
+            assert_eq!(demangle_name_with_hashes(mangled, false), *demangled);
+        }
+    }
+
+    #[test]
+    fn demangle_v0_names_keeps_disambiguators_with_hashes() {
+        assert_eq!(
+            demangle_name_with_hashes("_RNvMCs5twywQkEBMf_1vINtB2_7WrapperIBj_hEE3getB2_", true),
+            "v[3fc8ea1b5efe29c5]::Wrapper<v[3fc8ea1b5efe29c5]::Wrapper<u8>>::get"
+        );
+    }
+
+    #[test]
+    fn normalize_v0_names() {
+        assert_eq!(
+            normalize_v0_name("<alloc::vec::Vec<u8>>::push::<u8>::{closure#0}"),
+            "alloc::vec::Vec<u8>::push<u8>::{{closure}}"
+        );
+        assert_eq!(
+            normalize_v0_name("<fn() -> u8 as core::ops::FnOnce<()>>::call_once::{shim:vtable#0}"),
+            "<fn() -> u8 as core::ops::FnOnce<()>>::call_once::{{vtable.shim}}"
+        );
+        assert_eq!(normalize_v0_name("<[u8]>::len"), "[u8]::len");
+    }
+
+    #[test]
+    fn source_path_matches_relative_paths_against_base_directory() {
+        let base = Path::new("/home/me/project");
+        assert!(source_path_matches(
+            Path::new("src/main.rs"),
+            Path::new("/home/me/project/src/main.rs"),
+            base
+        ));
+        assert!(!source_path_matches(
+            Path::new("src/main.rs"),
+            Path::new("/elsewhere/src/main.rs"),
+            base
+        ));
+        assert!(source_path_matches(
+            Path::new("/home/me/project/src/main.rs"),
+            Path::new("./src/main.rs"),
+            base
+        ));
+        assert!(source_path_matches(
+            Path::new(r"C:\project\src\main.rs"),
+            Path::new("src/main.rs"),
+            base
+        ));
+        assert!(!source_path_matches(
+            Path::new("src/main.rs"),
+            Path::new(""),
+            base
+        ));
+    }
+}
@@ -1,8 +1,9 @@
-use super::{FileResolveStrategy, LineMapper};
+use super::{source_path_matches, FileResolveStrategy, LineMapper};
 use crate::platform::PathConverter;
 use once_cell::unsync::OnceCell;
 use pdb::FallibleIterator as _;
 use std::cell::RefMut;
+use std::collections::HashMap;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 
@@ -13,6 +14,10 @@ pub struct PDBLineMapper<'a> {
     base_directory: PathBuf,
     resolve_strategy: FileResolveStrategy,
     previous_module_index: usize,
+
+    /// The IPI stream, which has the names of inlined functions. Older PDBs don't have one.
+    ids: Option<pdb::IdInformation<'a>>,
+    inlinee_names: OnceCell<HashMap<u32, String>>,
 }
 
 impl<'a> PDBLineMapper<'a> {
@@ -24,6 +29,7 @@ impl<'a> PDBLineMapper<'a> {
     ) -> anyhow::Result<Self> {
         let debug_information = pdb.debug_information()?;
         let strings = pdb.string_table()?;
+        let ids = pdb.id_information().ok();
         let mut modules = Vec::new();
 
         let mut modules_iter = debug_information.modules()?;
@@ -40,52 +46,123 @@ impl<'a> PDBLineMapper<'a> {
             base_directory: PathBuf::from(base_directory),
             resolve_strategy,
             previous_module_index: 0,
+            ids,
+            inlinee_names: OnceCell::new(),
         })
     }
-}
 
-impl<'a> LineMapper for PDBLineMapper<'a> {
-    fn map_address_to_line(
+    /// Returns the module that contains line information for `address`, checking the module that
+    /// the previous address was found in first.
+    fn find_module(
         &mut self,
         address: u64,
         convert_path: &dyn PathConverter,
-    ) -> anyhow::Result<Option<(&Path, u32)>> {
-        if let Some(mapping) = self.modules.get(self.previous_module_index).map(|module| {
-            module.addr2line(
+    ) -> anyhow::Result<Option<&LazyModule<'a>>> {
+        let ids = self.ids.as_ref();
+        let inlinee_names = self
+            .inlinee_names
+            .get_or_try_init(|| load_inlinee_names(ids))?;
+
+        let indices = std::iter::once(self.previous_module_index)
+            .chain((0..self.modules.len()).filter(|&idx| idx != self.previous_module_index));
+        for idx in indices {
+            let module = if let Some(module) = self.modules.get(idx) {
+                module
+            } else {
+                continue;
+            };
+
+            let lines = module.lines(
                 &self.section_addresses,
                 &self.strings,
-                address,
+                inlinee_names,
                 &self.base_directory,
                 self.resolve_strategy,
                 convert_path,
-            )
-        }) {
-            if matches!(mapping, Ok(Some(_))) {
-                return mapping;
+            );
+
+            if matches!(lines, Ok(lines) if lines.contains(address)) {
+                self.previous_module_index = idx;
+                return Ok(self.modules.get(idx));
             }
         }
 
-        for (idx, module) in self.modules.iter().enumerate() {
-            if idx == self.previous_module_index {
-                continue;
-            }
+        Ok(None)
+    }
+}
+
+/// Reads the names of all of the functions in the IPI stream, which is how inline sites refer to
+/// the function that was inlined.
+fn load_inlinee_names(ids: Option<&pdb::IdInformation>) -> anyhow::Result<HashMap<u32, String>> {
+    let mut names = HashMap::new();
+    let ids = if let Some(ids) = ids {
+        ids
+    } else {
+        return Ok(names);
+    };
+
+    let mut ids_iter = ids.iter();
+    while let Some(id) = ids_iter.next()? {
+        let name = match id.parse() {
+            Ok(pdb::IdData::Function(function)) => function.name,
+            Ok(pdb::IdData::MemberFunction(function)) => function.name,
+            _ => continue,
+        };
+        names.insert(id.index().0, name.to_string().into_owned());
+    }
+
+    Ok(names)
+}
+
+impl<'a> LineMapper for PDBLineMapper<'a> {
+    fn map_address_to_line(
+        &mut self,
+        address: u64,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Option<(&Path, u32)>> {
+        Ok(self
+            .find_module(address, convert_path)?
+            .and_then(|module| module.lines.get())
+            .and_then(|lines| lines.lines_for_addr(address)))
+    }
 
-            let mapping = module.addr2line(
+    fn map_address_to_inlined_function(
+        &mut self,
+        address: u64,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Option<&str>> {
+        Ok(self
+            .find_module(address, convert_path)?
+            .and_then(|module| module.lines.get())
+            .and_then(|lines| lines.inlined_function_for_addr(address)))
+    }
+
+    fn map_line_to_addresses(
+        &mut self,
+        file: &Path,
+        line: u32,
+        convert_path: &dyn PathConverter,
+    ) -> anyhow::Result<Vec<Range<u64>>> {
+        let ids = self.ids.as_ref();
+        let inlinee_names = self
+            .inlinee_names
+            .get_or_try_init(|| load_inlinee_names(ids))?;
+
+        let mut ranges = Vec::new();
+        for module in self.modules.iter() {
+            // Modules without usable line information are skipped like in `find_module`.
+            if let Ok(lines) = module.lines(
                 &self.section_addresses,
                 &self.strings,
-                address,
+                inlinee_names,
                 &self.base_directory,
                 self.resolve_strategy,
                 convert_path,
-            );
-
-            if matches!(mapping, Ok(Some(_))) {
-                self.previous_module_index = idx;
-                return mapping;
+            ) {
+                lines.ranges_for_line(file, line, &self.base_directory, &mut ranges);
             }
         }
-
-        return Ok(None);
+        Ok(ranges)
     }
 }
 
@@ -106,95 +183,218 @@ impl<'a> LazyModule<'a> {
         &self,
         section_addresses: &[u64],
         string_table: &pdb::StringTable,
+        inlinee_names: &HashMap<u32, String>,
         _base_directory: &Path,
         _resolve_strategy: FileResolveStrategy,
         _convert_path: &dyn PathConverter,
     ) -> anyhow::Result<Lines> {
         let line_program = self.module.line_program()?;
+        let mut files = FileTable::default();
 
         let mut sequences = Vec::new();
         let mut line_prog_it = line_program.lines();
         while let Some(line_info) = line_prog_it.next()? {
-            if line_info.offset.section == 0 {
-                continue;
-            }
-
-            let addr_start = if let Some(section_addr) =
-                section_addresses.get(line_info.offset.section as usize - 1)
-            {
-                *section_addr as u64 + line_info.offset.offset as u64
-            } else {
-                continue;
-            };
-            let addr_end = addr_start + std::cmp::max(line_info.length.unwrap_or(1) as u64, 1);
+            let addr_range =
+                if let Some(addr_range) = line_addr_range(section_addresses, &line_info) {
+                    addr_range
+                } else {
+                    continue;
+                };
 
             sequences.push(Sequence {
-                addr_range: addr_start..addr_end,
+                addr_range,
                 line_range: line_info.line_start..(line_info.line_end + 1),
-                file_index: line_info.file_index.0,
+                file_index: files.index(&line_program, string_table, line_info.file_index)?,
             });
         }
         sequences.sort_by_key(|seq| seq.addr_range.start);
 
-        let mut files = Vec::new();
-        let mut files_it = line_program.files();
-        while let Some(file_info) = files_it.next()? {
-            // FIXME for now the base_directory and preferred resolve stretegy is ignored because I
-            // have not idea how I should get relative paths out of here. Going to have to find
-            // some way of guessing.
-            let file_name = string_table.get(file_info.name)?.to_string();
-            files.push(PathBuf::from(file_name.as_ref()));
+        // The line program only has the lines of the function that the code was inlined into, the
+        // lines of the inlined code come from the inline sites in the module's symbols and the
+        // module's inlinee line tables.
+        let mut inlinees = HashMap::new();
+        let mut inlinees_it = self.module.inlinees()?;
+        while let Some(inlinee) = inlinees_it.next()? {
+            inlinees.insert(inlinee.index().0, inlinee);
+        }
+
+        let mut inline_sequences: Vec<Vec<InlineSequence>> = Vec::new();
+        let mut functions = Vec::new();
+        let mut function_indices = HashMap::new();
+
+        let mut procedure_offset = None;
+        let mut depth: usize = 0;
+        let mut symbols_it = self.module.symbols()?;
+        while let Some(symbol) = symbols_it.next()? {
+            let site = match symbol.parse() {
+                Ok(pdb::SymbolData::Procedure(procedure)) => {
+                    procedure_offset = Some(procedure.offset);
+                    depth = 0;
+                    continue;
+                }
+                Ok(pdb::SymbolData::InlineSite(site)) => site,
+                Ok(pdb::SymbolData::InlineSiteEnd) => {
+                    depth = depth.saturating_sub(1);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            depth += 1;
+
+            let (procedure_offset, inlinee) =
+                match (procedure_offset, inlinees.get(&site.inlinee.0)) {
+                    (Some(procedure_offset), Some(inlinee)) => (procedure_offset, inlinee),
+                    _ => continue,
+                };
+
+            let function = *function_indices.entry(site.inlinee.0).or_insert_with(|| {
+                let name = inlinee_names
+                    .get(&site.inlinee.0)
+                    .cloned()
+                    .unwrap_or_else(|| format!("<inlinee {:#x}>", site.inlinee.0));
+                functions.push(name);
+                functions.len() - 1
+            });
+
+            if inline_sequences.len() < depth {
+                inline_sequences.resize_with(depth, Vec::new);
+            }
+
+            let mut inlinee_lines_it = inlinee.lines(procedure_offset, &site);
+            while let Some(line_info) = inlinee_lines_it.next()? {
+                let addr_range =
+                    if let Some(addr_range) = line_addr_range(section_addresses, &line_info) {
+                        addr_range
+                    } else {
+                        continue;
+                    };
+
+                inline_sequences[depth - 1].push(InlineSequence {
+                    addr_range,
+                    line: line_info.line_start,
+                    file_index: files.index(&line_program, string_table, line_info.file_index)?,
+                    function,
+                });
+            }
+        }
+
+        for sequences in inline_sequences.iter_mut() {
+            sequences.sort_by_key(|seq| seq.addr_range.start);
         }
 
         Ok(Lines {
             sequences: sequences.into_boxed_slice(),
-            files: files.into_boxed_slice(),
+            inline_sequences: inline_sequences
+                .into_iter()
+                .map(Vec::into_boxed_slice)
+                .collect(),
+            functions: functions.into_boxed_slice(),
+            files: files.paths.into_boxed_slice(),
         })
     }
 
-    pub fn addr2line(
+    pub fn lines(
         &self,
         section_addresses: &[u64],
         string_table: &pdb::StringTable,
-        addr: u64,
+        inlinee_names: &HashMap<u32, String>,
         base_directory: &Path,
         resolve_strategy: FileResolveStrategy,
         convert_path: &dyn PathConverter,
-    ) -> anyhow::Result<Option<(&Path, u32)>> {
-        self.lines
-            .get_or_try_init(|| {
-                self.load_lines(
-                    section_addresses,
-                    string_table,
-                    base_directory,
-                    resolve_strategy,
-                    convert_path,
-                )
-            })
-            .map(|lines| lines.lines_for_addr(addr))
+    ) -> anyhow::Result<&Lines> {
+        self.lines.get_or_try_init(|| {
+            self.load_lines(
+                section_addresses,
+                string_table,
+                inlinee_names,
+                base_directory,
+                resolve_strategy,
+                convert_path,
+            )
+        })
+    }
+}
+
+/// Returns the range of addresses covered by a line record.
+fn line_addr_range(section_addresses: &[u64], line_info: &pdb::LineInfo) -> Option<Range<u64>> {
+    if line_info.offset.section == 0 {
+        return None;
+    }
+
+    let section_addr = section_addresses.get(line_info.offset.section as usize - 1)?;
+    let addr_start = *section_addr as u64 + line_info.offset.offset as u64;
+    let addr_end = addr_start + std::cmp::max(line_info.length.unwrap_or(1) as u64, 1);
+    Some(addr_start..addr_end)
+}
+
+/// The source files referenced by the line records of a module. Line records refer to files by
+/// their offset in the module's file checksums, which is mapped to an index into `paths` here.
+#[derive(Default)]
+struct FileTable {
+    paths: Vec<PathBuf>,
+    indices: HashMap<u32, u32>,
+}
+
+impl FileTable {
+    fn index(
+        &mut self,
+        line_program: &pdb::LineProgram,
+        string_table: &pdb::StringTable,
+        file_index: pdb::FileIndex,
+    ) -> anyhow::Result<u32> {
+        if let Some(&index) = self.indices.get(&file_index.0) {
+            return Ok(index);
+        }
+
+        // FIXME for now the base_directory and preferred resolve stretegy is ignored because I
+        // have not idea how I should get relative paths out of here. Going to have to find
+        // some way of guessing.
+        let file_info = line_program.get_file_info(file_index)?;
+        let file_name = string_table.get(file_info.name)?.to_string();
+        self.paths.push(PathBuf::from(file_name.as_ref()));
+
+        let index = self.paths.len() as u32 - 1;
+        self.indices.insert(file_index.0, index);
+        Ok(index)
     }
 }
 
 struct Lines {
     sequences: Box<[Sequence]>,
+
+    /// Line sequences of inlined code, indexed by how deeply the inline site is nested inside of
+    /// its procedure. The sequences at each depth don't overlap and are sorted by address.
+    inline_sequences: Box<[Box<[InlineSequence]>]>,
+
+    /// Names of the inlined functions that `InlineSequence::function` indexes.
+    functions: Box<[String]>,
+
     files: Box<[PathBuf]>,
 }
 
 impl Lines {
+    fn contains(&self, addr: u64) -> bool {
+        find_sequence(&self.sequences, addr, |seq| &seq.addr_range).is_some()
+    }
+
+    /// Returns the sequence of the most deeply nested inline site that contains `addr`.
+    fn inline_sequence_for_addr(&self, addr: u64) -> Option<&InlineSequence> {
+        self.inline_sequences
+            .iter()
+            .rev()
+            .find_map(|sequences| find_sequence(sequences, addr, |seq| &seq.addr_range))
+    }
+
     fn lines_for_addr(&self, addr: u64) -> Option<(&Path, u32)> {
-        let sequence = self
-            .sequences
-            .binary_search_by(|probe| {
-                if probe.addr_range.start > addr {
-                    std::cmp::Ordering::Greater
-                } else if probe.addr_range.end <= addr {
-                    std::cmp::Ordering::Less
-                } else {
-                    std::cmp::Ordering::Equal
-                }
-            })
-            .ok()
-            .and_then(|seq_idx| self.sequences.get(seq_idx))?;
+        if let Some(inline_sequence) = self.inline_sequence_for_addr(addr) {
+            return self
+                .files
+                .get(inline_sequence.file_index as usize)
+                .map(|f| (f.as_path(), inline_sequence.line));
+        }
+
+        let sequence = find_sequence(&self.sequences, addr, |seq| &seq.addr_range)?;
 
         let line = sequence.line_range.start;
 
@@ -204,6 +404,65 @@ impl Lines {
             .get(sequence.file_index as usize)
             .map(|f| (f.as_path(), line))
     }
+
+    /// Adds the address ranges of the code for `line` of the source file `file` to `ranges`,
+    /// including code that was inlined from that line.
+    fn ranges_for_line(
+        &self,
+        file: &Path,
+        line: u32,
+        base_directory: &Path,
+        ranges: &mut Vec<Range<u64>>,
+    ) {
+        let matching_files = self
+            .files
+            .iter()
+            .map(|path| source_path_matches(path, file, base_directory))
+            .collect::<Vec<bool>>();
+        if !matching_files.contains(&true) {
+            return;
+        }
+        let file_matches = |file_index: u32| {
+            matching_files
+                .get(file_index as usize)
+                .copied()
+                .unwrap_or(false)
+        };
+
+        for sequence in self.sequences.iter() {
+            if sequence.line_range.start == line && file_matches(sequence.file_index) {
+                ranges.push(sequence.addr_range.clone());
+            }
+        }
+
+        for sequence in self.inline_sequences.iter().flat_map(|seqs| seqs.iter()) {
+            if sequence.line == line && file_matches(sequence.file_index) {
+                ranges.push(sequence.addr_range.clone());
+            }
+        }
+    }
+
+    fn inlined_function_for_addr(&self, addr: u64) -> Option<&str> {
+        self.inline_sequence_for_addr(addr)
+            .and_then(|seq| self.functions.get(seq.function))
+            .map(|name| name.as_str())
+    }
+}
+
+fn find_sequence<T>(sequences: &[T], addr: u64, range: impl Fn(&T) -> &Range<u64>) -> Option<&T> {
+    sequences
+        .binary_search_by(|probe| {
+            let probe = range(probe);
+            if probe.start > addr {
+                std::cmp::Ordering::Greater
+            } else if probe.end <= addr {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .ok()
+        .and_then(|seq_idx| sequences.get(seq_idx))
 }
 
 /// Maps a contiguous region of bytes to lines.
@@ -212,3 +471,93 @@ struct Sequence {
     line_range: Range<u32>,
     file_index: u32,
 }
+
+/// Maps a contiguous region of bytes of inlined code to a line in the inlined function.
+struct InlineSequence {
+    addr_range: Range<u64>,
+    line: u32,
+    file_index: u32,
+    function: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InlineSequence, Lines, Sequence};
+    use std::path::{Path, PathBuf};
+
+    /// `main` (src/main.rs:10) calls `Option::map` (option.rs:100) at 0x1010..0x1030, which calls
+    /// `Result::ok` (result.rs:200) at 0x1018..0x1020. Everything was inlined into `main`.
+    fn lines() -> Lines {
+        Lines {
+            sequences: vec![Sequence {
+                addr_range: 0x1000..0x1040,
+                line_range: 10..12,
+                file_index: 0,
+            }]
+            .into_boxed_slice(),
+            inline_sequences: vec![
+                vec![InlineSequence {
+                    addr_range: 0x1010..0x1030,
+                    line: 100,
+                    file_index: 1,
+                    function: 0,
+                }]
+                .into_boxed_slice(),
+                vec![InlineSequence {
+                    addr_range: 0x1018..0x1020,
+                    line: 200,
+                    file_index: 2,
+                    function: 1,
+                }]
+                .into_boxed_slice(),
+            ]
+            .into_boxed_slice(),
+            functions: vec![String::from("Option::map"), String::from("Result::ok")]
+                .into_boxed_slice(),
+            files: vec![
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("option.rs"),
+                PathBuf::from("result.rs"),
+            ]
+            .into_boxed_slice(),
+        }
+    }
+
+    #[test]
+    fn deepest_inline_site_wins() {
+        let lines = lines();
+        assert_eq!(
+            lines.lines_for_addr(0x1018),
+            Some((Path::new("result.rs"), 200))
+        );
+        assert_eq!(lines.inlined_function_for_addr(0x1018), Some("Result::ok"));
+
+        assert_eq!(
+            lines.lines_for_addr(0x1010),
+            Some((Path::new("option.rs"), 100))
+        );
+        assert_eq!(lines.inlined_function_for_addr(0x1010), Some("Option::map"));
+    }
+
+    #[test]
+    fn outer_sequence_after_inline_site() {
+        let lines = lines();
+        assert_eq!(
+            lines.lines_for_addr(0x1020),
+            Some((Path::new("option.rs"), 100))
+        );
+        assert_eq!(
+            lines.lines_for_addr(0x1030),
+            Some((Path::new("src/main.rs"), 10))
+        );
+        assert_eq!(lines.lines_for_addr(0x1040), None);
+    }
+
+    #[test]
+    fn no_inlined_function_outside_of_inline_sites() {
+        let lines = lines();
+        assert_eq!(lines.inlined_function_for_addr(0x1000), None);
+        assert_eq!(lines.inlined_function_for_addr(0x1030), None);
+        assert_eq!(lines.inlined_function_for_addr(0x2000), None);
+    }
+}
@@ -1,10 +1,13 @@
 use super::dwarf::DwarfLineMapper;
 use super::{
-    demangle_name, Binary, BinaryArch, BinaryBits, BinaryData, BinaryEndian, FilePDB,
-    FileResolveStrategy, LineMapper, ObjectExt, StringArena, Symbol,
+    demangle_name, infer_symbol_sizes, Binary, BinaryArch, BinaryBits, BinaryData, BinaryEndian,
+    FilePDB, FileResolveStrategy, LineMapper, MappingKind, ObjectExt, StringArena, Symbol,
 };
 use anyhow::Context as _;
-use goblin::pe::PE;
+use goblin::pe::debug::CodeviewPDB70DebugInfo;
+use goblin::pe::section_table::SectionTable;
+use goblin::pe::symbol::Symbol as CoffSymbol;
+use goblin::pe::{Coff, PE};
 use std::borrow::Cow;
 use std::cell::RefMut;
 use std::path::{Path, PathBuf};
@@ -13,6 +16,7 @@ pub fn analyze_pe<'a>(
     pe: PE<'a>,
     data: &'a BinaryData,
     binary_path: &Path,
+    debug_file: Option<&Path>,
     _load_debug_info: bool,
 ) -> anyhow::Result<Binary<'a>> {
     let endian = BinaryEndian::Little;
@@ -26,44 +30,21 @@ pub fn analyze_pe<'a>(
 
     let mut symbols = Vec::new();
 
-    // We check a few places for symbols when analyzing PE files.
-    // The exports list (FIXME), the COFF symbol table, DLL exports (FIXME), and
-    // PDB debug information (FIXME).
-
-    // FIXME I'm not sure if I should be using these actually if its for DLLs. I'm also not sure if
-    // it has the same problem as the COFF symbols and some of the fields aren't reliable.  For now,
-    // just parsing COFF symbol tables and PDBs.
-    //
-    // for export in pe.exports.iter() {
-    //     let sym_name = if let Some(name) = export.name {
-    //         name
-    //     } else {
-    //         continue;
-    //     };
-    //     let sym_name_demangled = demangle_name(sym_name);
-
-    //     symbols.push(Symbol {
-    //         original_name: Cow::from(sym_name),
-    //         demangled_name: sym_name_demangled,
-    //         short_demangled_name: Default::default(),
-
-    //         addr: export.rva as u64,
-    //         offset: export.offset,
-    //         size: export.size,
-    //     });
-    // }
-
+    // We check a few places for symbols when analyzing PE files: the COFF symbol table, PDB debug
+    // information and the exports of DLLs.
     get_coff_symbols(&pe, data.data(), &mut symbols)?;
 
-    let debug_data_pdb = pe
+    let codeview = pe
         .debug_data
         .as_ref()
-        .and_then(|data| data.codeview_pdb70_debug_info.as_ref())
+        .and_then(|data| data.codeview_pdb70_debug_info.as_ref());
+    let debug_data_pdb = codeview
         .and_then(|codeview| std::str::from_utf8(codeview.filename).ok())
-        .map(|pdb_str| PathBuf::from(pdb_str));
+        .map(|pdb_str| PathBuf::from(pdb_str.trim_end_matches('\0')));
 
-    // FIXME allow passing PDB file as an argument somehow which would override this:
-    let pdb_path = if Some(true) == debug_data_pdb.as_ref().map(|p| p.exists()) {
+    let pdb_path = if let Some(debug_file) = debug_file {
+        Some(debug_file.to_path_buf())
+    } else if Some(true) == debug_data_pdb.as_ref().map(|p| p.exists()) {
         debug_data_pdb
     } else {
         find_pdb_path(binary_path)
@@ -73,6 +54,10 @@ pub fn analyze_pe<'a>(
         let pdb_file = std::fs::File::open(pdb_path)
             .with_context(|| format!("failed to open file `{}`", pdb_path.display()))?;
         data.set_pdb(pdb::PDB::open(pdb_file)?);
+
+        if let Some(codeview) = codeview {
+            check_pdb_signature(codeview, &mut data.pdb_mut(), pdb_path);
+        }
         // let mut pdb = pdb::PDB::open(pdb_file)?;
 
         get_pdb_symbols(
@@ -93,16 +78,321 @@ pub fn analyze_pe<'a>(
         }
     };
 
+    // Exports are only used for functions that weren't found in the COFF symbols or the PDB
+    // because they don't have a size.
+    get_export_symbols(&pe_ext.pe, &mut symbols);
+    let import_symbols = get_import_symbols(&pe_ext.pe);
+
+    let mappings = get_thumb_mappings(pe_ext.pe.header.coff_header.machine, &mut symbols);
+
     Ok(Binary {
         data,
         bits,
         arch,
         endian,
         symbols,
+        mappings,
+        local_entries: Vec::new(),
+        relocations: Vec::new(),
+        import_symbols,
         object: ObjectExt::PE(pe_ext),
     })
 }
 
+/// Prints a warning if the GUID and age of a PDB don't match the ones that the linker embedded in
+/// the binary, which means that the PDB was generated for a different build.
+fn check_pdb_signature(codeview: &CodeviewPDB70DebugInfo, pdb: &mut FilePDB, pdb_path: &Path) {
+    let info = if let Ok(info) = pdb.pdb_information() {
+        info
+    } else {
+        return;
+    };
+
+    // The first three fields of the GUID are stored in little endian in the binary but the bytes of
+    // a `Uuid` are in big endian.
+    let mut guid = codeview.signature;
+    guid[0..4].reverse();
+    guid[4..6].reverse();
+    guid[6..8].reverse();
+
+    // The age in the PDB information stream may be bumped by tools that modify the PDB, so the age
+    // that the linker wrote to the DBI stream is compared instead. Old PDBs don't have that one.
+    let age = pdb
+        .debug_information()
+        .ok()
+        .and_then(|dbi| dbi.age())
+        .unwrap_or(info.age);
+    if info.guid.as_bytes() != &guid || age != codeview.age {
+        eprintln!(
+            "warning: the PDB `{}` does not match the binary (GUID {} age {}, expected GUID {} age {}).",
+            pdb_path.display(),
+            info.guid,
+            age,
+            uuid_string(&guid),
+            codeview.age,
+        );
+    }
+}
+
+fn uuid_string(bytes: &[u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Adds a symbol for each function exported by the PE that doesn't have one yet. Exports don't
+/// have a size, so it is guessed from the next symbol or the end of the export's section.
+fn get_export_symbols<'a>(pe: &PE<'a>, symbols: &mut Vec<Symbol<'a>>) {
+    use goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE;
+
+    let mut known_addrs: Vec<u64> = symbols.iter().map(|sym| sym.addr).collect();
+    known_addrs.sort_unstable();
+
+    let mut unsized_symbols = Vec::new();
+    for export in pe.exports.iter() {
+        // Forwarded exports point at a string naming the function in another DLL.
+        let sym_name = match export.name {
+            Some(name) if export.reexport.is_none() => name,
+            _ => continue,
+        };
+
+        let rva = export.rva as u32;
+        let section = if let Some(section) = pe.sections.iter().find(|section| {
+            rva >= section.virtual_address && rva < (section.virtual_address + section.virtual_size)
+        }) {
+            section
+        } else {
+            continue;
+        };
+        if section.characteristics & IMAGE_SCN_MEM_EXECUTE == 0 {
+            continue;
+        }
+
+        let sym_addr = pe.image_base as u64 + rva as u64;
+        if known_addrs.binary_search(&sym_addr).is_ok() {
+            continue;
+        }
+
+        let section_end =
+            pe.image_base as u64 + (section.virtual_address + section.virtual_size) as u64;
+        unsized_symbols.push((symbols.len(), section_end));
+        symbols.push(Symbol {
+            original_name: Cow::from(sym_name),
+            demangled_name: demangle_name(sym_name),
+            short_demangled_name: Default::default(),
+
+            addr: sym_addr,
+            offset: (rva - section.virtual_address + section.pointer_to_raw_data) as usize,
+            size: 0,
+            size_inferred: true,
+            archive_member: None,
+        });
+    }
+
+    infer_symbol_sizes(symbols, &unsized_symbols);
+}
+
+/// Returns a symbol named like `KERNEL32!CreateFileW` for each slot of the import address table,
+/// sorted by the address of the slot.
+fn get_import_symbols<'a>(pe: &PE<'a>) -> Vec<Symbol<'a>> {
+    let mut import_symbols = Vec::with_capacity(pe.imports.len());
+
+    for import in pe.imports.iter() {
+        let dll = import.dll.rsplitn(2, '.').last().unwrap_or(import.dll);
+        let name = format!("{}!{}", dll, import.name);
+
+        // goblin puts the RVA of the import's slot in the import address table in `offset`.
+        let slot_rva = import.offset as u32;
+        let slot_offset = if let Some(section) = pe.sections.iter().find(|section| {
+            slot_rva >= section.virtual_address
+                && slot_rva < (section.virtual_address + section.size_of_raw_data)
+        }) {
+            (slot_rva - section.virtual_address + section.pointer_to_raw_data) as usize
+        } else {
+            continue;
+        };
+
+        import_symbols.push(Symbol {
+            original_name: Cow::from(name.clone()),
+            demangled_name: Cow::from(name),
+            short_demangled_name: Default::default(),
+
+            addr: pe.image_base as u64 + slot_rva as u64,
+            offset: slot_offset,
+            size: import.size,
+            size_inferred: false,
+            archive_member: None,
+        });
+    }
+
+    import_symbols.sort_by_key(|sym| sym.addr);
+    import_symbols
+}
+
+/// Windows on ARM only runs Thumb code, so every function is marked as Thumb code.
+fn get_thumb_mappings(machine: u16, symbols: &mut [Symbol]) -> Vec<(u64, MappingKind)> {
+    let mut mappings = Vec::new();
+    if machine == goblin::pe::header::COFF_MACHINE_ARMNT
+        || machine == goblin::pe::header::COFF_MACHINE_THUMB
+    {
+        for symbol in symbols.iter_mut() {
+            symbol.addr &= !1;
+            mappings.push((symbol.addr, MappingKind::Thumb));
+        }
+        mappings.sort_unstable_by_key(|m| m.0);
+        mappings.dedup_by_key(|m| m.0);
+    }
+    mappings
+}
+
+/// A COFF object file (`.obj`), which is what the members of MSVC static libraries and rlibs
+/// are.
+#[derive(Debug)]
+pub struct CoffExt<'a> {
+    pub coff: Coff<'a>,
+
+    /// Sections in an object file don't have an address yet, so they are laid out one after the
+    /// other starting at these addresses.
+    pub section_addrs: Vec<u64>,
+}
+
+/// Parses `data` as a COFF object file. There is no magic number at the start of these files, so
+/// this only accepts the file if the machine type is one that we know of.
+pub fn parse_coff_object(data: &[u8]) -> Option<Coff<'_>> {
+    let coff = Coff::parse(data).ok()?;
+    match BinaryArch::from_coff_machine(coff.header.machine, BinaryBits::Bits32) {
+        None | Some(BinaryArch::Unknown) => None,
+        Some(_) => Some(coff),
+    }
+}
+
+pub fn analyze_coff<'a>(coff: Coff<'a>, data: &'a BinaryData) -> anyhow::Result<Binary<'a>> {
+    use goblin::pe::header::{COFF_MACHINE_ARM64, COFF_MACHINE_X86_64};
+    use goblin::pe::symbol::{IMAGE_SYM_CLASS_EXTERNAL, IMAGE_SYM_CLASS_STATIC};
+
+    let endian = BinaryEndian::Little;
+    let bits = match coff.header.machine {
+        COFF_MACHINE_X86_64 | COFF_MACHINE_ARM64 => BinaryBits::Bits64,
+        _ => BinaryBits::Bits32,
+    };
+    let arch = BinaryArch::from_coff_machine(coff.header.machine, bits)
+        .expect("[FIXME] unrecognized arch");
+
+    let mut section_addrs = Vec::with_capacity(coff.sections.len());
+    let mut next_addr = 0u64;
+    for section in coff.sections.iter() {
+        // The alignment is stored as a power of two plus one in bits 20-23 of the
+        // characteristics.
+        let align = match (section.characteristics >> 20) & 0xF {
+            0 => 1,
+            n => 1u64 << (n - 1),
+        };
+        next_addr = (next_addr + align - 1) & !(align - 1);
+        section_addrs.push(next_addr);
+        next_addr += section.size_of_raw_data as u64;
+    }
+
+    // Symbols are collected along with the index of their section first so that their sizes
+    // can be computed.
+    let mut section_symbols = Vec::new();
+    for (_sym_index, inline_name, symbol) in coff.symbols.iter() {
+        if symbol.typ != 0x20
+            || symbol.section_number < 1
+            || (symbol.storage_class != IMAGE_SYM_CLASS_EXTERNAL
+                && symbol.storage_class != IMAGE_SYM_CLASS_STATIC)
+        {
+            continue;
+        }
+
+        let sym_name = if let Some(name) = inline_name {
+            name
+        } else if let Some(Ok(name)) = symbol
+            .name_offset()
+            .and_then(|off| coff.strings.get(off as usize))
+        {
+            name
+        } else {
+            continue;
+        };
+
+        let section_idx = symbol.section_number as usize - 1;
+        let section = if let Some(section) = coff.sections.get(section_idx) {
+            section
+        } else {
+            continue;
+        };
+
+        section_symbols.push((
+            section_idx,
+            Symbol {
+                original_name: Cow::from(sym_name),
+                demangled_name: demangle_name(sym_name),
+                short_demangled_name: Default::default(),
+
+                addr: section_addrs[section_idx] + symbol.value as u64,
+                offset: (section.pointer_to_raw_data + symbol.value) as usize,
+                size: 0,
+                size_inferred: true,
+                archive_member: None,
+            },
+        ));
+    }
+
+    // COFF symbols don't have a size, so each function is assumed to end where the next one
+    // starts, or at the end of its section.
+    section_symbols.sort_by_key(|(_, sym)| sym.addr);
+    let next_addrs = section_symbols
+        .iter()
+        .skip(1)
+        .map(|(_, sym)| Some(sym.addr))
+        .chain(std::iter::once(None))
+        .collect::<Vec<_>>();
+    let mut symbols = Vec::with_capacity(section_symbols.len());
+    for ((section_idx, mut symbol), next_addr) in section_symbols.into_iter().zip(next_addrs) {
+        let section_end =
+            section_addrs[section_idx] + coff.sections[section_idx].size_of_raw_data as u64;
+        let end = next_addr
+            .map(|next_addr| std::cmp::min(next_addr, section_end))
+            .unwrap_or(section_end);
+        symbol.size = (end - symbol.addr) as usize;
+        symbols.push(symbol);
+    }
+
+    let mappings = get_thumb_mappings(coff.header.machine, &mut symbols);
+
+    // FIXME apply the relocations of COFF objects like is done for ELF objects.
+    Ok(Binary {
+        data,
+        bits,
+        arch,
+        endian,
+        symbols,
+        mappings,
+        local_entries: Vec::new(),
+        relocations: Vec::new(),
+        import_symbols: Vec::new(),
+        object: ObjectExt::Coff(CoffExt {
+            coff,
+            section_addrs,
+        }),
+    })
+}
+
+pub(super) fn coff_addr_to_offset(coff: &CoffExt, addr: u64) -> Option<usize> {
+    coff.coff
+        .sections
+        .iter()
+        .zip(coff.section_addrs.iter())
+        .find(|(section, &start)| addr >= start && addr < start + section.size_of_raw_data as u64)
+        .map(|(section, &start)| (addr - start) as usize + section.pointer_to_raw_data as usize)
+}
+
 /// Attempts to find the PDB file for the binary.
 /// This will just search for a file in the same directory as binary_path with the same filename
 /// but with the .pdb extension.
@@ -190,6 +480,8 @@ fn get_pdb_symbols<'a, S: 'a + pdb::Source<'a>>(
                         offset: sym_offset,
                         addr: sym_address,
                         size: sym_data.len as usize,
+                        size_inferred: false,
+                        archive_member: None,
                     });
                 }
 
@@ -209,84 +501,92 @@ fn get_coff_symbols<'a>(
     let maybe_symtab = pe.header.coff_header.symbols(data).ok();
     let maybe_strtab = pe.header.coff_header.strings(data).ok();
 
-    let first_symbol_index = symbols.len();
-
     if let Some(symtab) = maybe_symtab {
-        for (_sym_index, inline_name, symbol) in symtab.iter() {
-            if !symbol.is_function_definition() && symbol.typ != 0x20 {
-                continue;
-            }
-
-            // FIXME for now we skip symbols that are sections, but I think the sections can also
-            // actually just contain the function (???) and in this the entire section should be
-            // used. I'm not sure if that is the case though.
-            if symbol.value == 0 {
-                continue;
-            }
-
-            let sym_name = if let Some(name) = inline_name {
-                name
-            } else if let Some(ref strtab) = maybe_strtab {
-                if let Some(Ok(name)) = symbol
-                    .name_offset()
-                    .and_then(|off| strtab.get(off as usize))
-                {
+        let named_symbols = symtab
+            .iter()
+            .filter_map(|(_sym_index, inline_name, symbol)| {
+                let sym_name = if let Some(name) = inline_name {
                     name
                 } else {
-                    continue;
-                }
-            } else {
-                continue;
-            };
+                    let strtab = maybe_strtab.as_ref()?;
+                    symbol
+                        .name_offset()
+                        .and_then(|off| strtab.get(off as usize))?
+                        .ok()?
+                };
+                Some((sym_name, symbol))
+            });
+        add_coff_function_symbols(named_symbols, &pe.sections, pe.image_base as u64, symbols);
+    }
 
-            if sym_name.is_empty() {
-                continue;
-            }
+    Ok(())
+}
 
-            let (sym_addr, sym_offset) = if symbol.section_number >= 1 {
-                let section = &pe.sections[symbol.section_number as usize - 1];
-
-                if symbol.storage_class == goblin::pe::symbol::IMAGE_SYM_CLASS_STATIC
-                    || symbol.storage_class == goblin::pe::symbol::IMAGE_SYM_CLASS_EXTERNAL
-                    || symbol.storage_class == goblin::pe::symbol::IMAGE_SYM_CLASS_LABEL
-                {
-                    (
-                        pe.image_base as u64 + (section.virtual_address + symbol.value) as u64,
-                        (section.pointer_to_raw_data + symbol.value) as usize,
-                    )
-                } else {
-                    continue;
-                }
-            } else {
-                continue;
-            };
-            let sym_name_demangled = demangle_name(sym_name);
+/// Adds a symbol for each function in a COFF symbol table. Using the information in the COFF like
+/// AuxFunctionDefinition::total_size is unreliable, so instead the size of a function is guessed
+/// from the next symbol or the end of the function's section.
+fn add_coff_function_symbols<'a, I>(
+    coff_symbols: I,
+    sections: &[SectionTable],
+    image_base: u64,
+    symbols: &mut Vec<Symbol<'a>>,
+) where
+    I: Iterator<Item = (&'a str, CoffSymbol)>,
+{
+    use goblin::pe::symbol::{
+        IMAGE_SYM_CLASS_EXTERNAL, IMAGE_SYM_CLASS_LABEL, IMAGE_SYM_CLASS_STATIC,
+    };
 
-            symbols.push(Symbol {
-                original_name: Cow::from(sym_name),
-                demangled_name: sym_name_demangled,
-                short_demangled_name: Default::default(),
+    let mut unsized_symbols = Vec::new();
+    for (sym_name, symbol) in coff_symbols {
+        if !symbol.is_function_definition() && symbol.typ != 0x20 {
+            continue;
+        }
 
-                addr: sym_addr,
-                offset: sym_offset,
-                size: 0,
-            });
+        // FIXME for now we skip symbols that are sections, but I think the sections can also
+        // actually just contain the function (???) and in this the entire section should be
+        // used. I'm not sure if that is the case though.
+        if symbol.value == 0 {
+            continue;
         }
-    }
 
-    // Using the information in the COFF like AuxFunctionDefinition::total_size is unreliable, so
-    // instead we just sort the functions by address and guess that the size of the function is its
-    // start address subtracted from the start address of the next function. This works for the
-    // most part :P.
+        if sym_name.is_empty() {
+            continue;
+        }
 
-    (&mut symbols[first_symbol_index..]).sort_by_key(|sym| sym.addr);
-    if symbols.len() - first_symbol_index > 1 {
-        for idx in first_symbol_index..(symbols.len() - 1) {
-            symbols[idx].size = (symbols[idx + 1].addr - symbols[idx].addr) as usize;
+        let section = if symbol.section_number >= 1 {
+            if let Some(section) = sections.get(symbol.section_number as usize - 1) {
+                section
+            } else {
+                continue;
+            }
+        } else {
+            continue;
+        };
+
+        if symbol.storage_class != IMAGE_SYM_CLASS_STATIC
+            && symbol.storage_class != IMAGE_SYM_CLASS_EXTERNAL
+            && symbol.storage_class != IMAGE_SYM_CLASS_LABEL
+        {
+            continue;
         }
+
+        let section_end = image_base + (section.virtual_address + section.virtual_size) as u64;
+        unsized_symbols.push((symbols.len(), section_end));
+        symbols.push(Symbol {
+            original_name: Cow::from(sym_name),
+            demangled_name: demangle_name(sym_name),
+            short_demangled_name: Default::default(),
+
+            addr: image_base + (section.virtual_address + symbol.value) as u64,
+            offset: (section.pointer_to_raw_data + symbol.value) as usize,
+            size: 0,
+            size_inferred: false,
+            archive_member: None,
+        });
     }
 
-    Ok(())
+    infer_symbol_sizes(symbols, &unsized_symbols);
 }
 
 pub(super) fn pe_line_mapper<'a>(
@@ -368,6 +668,26 @@ fn pe_dwarf_line_mapper<'a>(
     Ok(mapper)
 }
 
+pub(super) fn pe_addr_to_offset(pe: &PEExt, addr: u64) -> Option<usize> {
+    let rva = addr.checked_sub(pe.pe.image_base as u64)?;
+
+    pe.pe
+        .sections
+        .iter()
+        .find(|section| {
+            let start = section.virtual_address as u64;
+            // Some linkers leave `virtual_size` as 0, in which case only the raw size is known.
+            let size = match section.virtual_size {
+                0 => section.size_of_raw_data,
+                virtual_size => std::cmp::min(virtual_size, section.size_of_raw_data),
+            } as u64;
+            rva >= start && rva < (start + size)
+        })
+        .map(|section| {
+            (rva - section.virtual_address as u64) as usize + section.pointer_to_raw_data as usize
+        })
+}
+
 fn get_section_by_name<'a>(
     pe: &PEExt<'a>,
     binary: &'a [u8],
@@ -401,3 +721,54 @@ pub enum PEDebug {
     Dwarf,
     PDB,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{add_coff_function_symbols, CoffSymbol, SectionTable};
+    use crate::binary::{test_symbol, test_symbol_layout};
+    use goblin::pe::symbol::{IMAGE_SYM_CLASS_EXTERNAL, IMAGE_SYM_CLASS_STATIC};
+
+    fn coff_symbol(value: u32, typ: u16, storage_class: u8) -> CoffSymbol {
+        CoffSymbol {
+            name: [0; 8],
+            value,
+            section_number: 1,
+            typ,
+            storage_class,
+            number_of_aux_symbols: 0,
+        }
+    }
+
+    #[test]
+    fn coff_symbols_end_at_next_symbol_or_section_end() {
+        let sections = [SectionTable {
+            virtual_address: 0x1000,
+            virtual_size: 0x100,
+            pointer_to_raw_data: 0x400,
+            ..Default::default()
+        }];
+
+        // A symbol that was already found elsewhere keeps its size, even if it doesn't have one.
+        let mut symbols = vec![test_symbol("other", 0x2000, 0)];
+        let coff_symbols = vec![
+            ("main", coff_symbol(0x10, 0x20, IMAGE_SYM_CLASS_EXTERNAL)),
+            ("data", coff_symbol(0x40, 0, IMAGE_SYM_CLASS_EXTERNAL)),
+            ("helper", coff_symbol(0x80, 0x20, IMAGE_SYM_CLASS_STATIC)),
+        ];
+        add_coff_function_symbols(
+            coff_symbols.into_iter(),
+            &sections,
+            0x4000_0000,
+            &mut symbols,
+        );
+
+        assert_eq!(
+            test_symbol_layout(&symbols),
+            [
+                ("other", 0x2000, 0x2000, 0, false),
+                ("main", 0x4000_1010, 0x410, 0x70, true),
+                ("helper", 0x4000_1080, 0x480, 0x80, true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Split DWARF (`-C split-debuginfo=unpacked` or `packed`, `-gsplit-dwarf` in C compilers).
+//!
+//! The executable only contains skeleton compilation units with the address ranges and line
+//! programs, while the rest of each unit lives in a `.dwo` file next to the object file it was
+//! compiled into, or in a single `.dwp` package next to the executable. Both DWARF 5 split units
+//! and the GNU extension to DWARF 4 (`DW_AT_GNU_dwo_name`, which rustc emits) are supported.
+
+use super::elf::{get_debug_section, get_section_by_name};
+use super::BinaryEndian;
+use gimli::Reader as _;
+use goblin::elf::Elf;
+use once_cell::unsync::OnceCell;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Section identifiers used in the `.debug_cu_index` section of a `.dwp` package. These are the
+/// same for the GNU (version 2) and DWARF 5 formats.
+const DW_SECT_INFO: u32 = 1;
+const DW_SECT_ABBREV: u32 = 3;
+const DW_SECT_LINE: u32 = 4;
+const DW_SECT_STR_OFFSETS: u32 = 6;
+
+pub type SplitReader<'a> = gimli::EndianSlice<'a, gimli::RunTimeEndian>;
+
+pub struct SplitDwarf {
+    binary_path: PathBuf,
+    endian: gimli::RunTimeEndian,
+
+    /// The `.dwp` package next to the binary, which is only loaded once.
+    dwp: OnceCell<Option<DwpPackage>>,
+
+    /// `.dwo` files that were already loaded (or that couldn't be loaded) by path.
+    dwo_files: RefCell<HashMap<PathBuf, Option<Rc<SplitSections>>>>,
+
+    /// Names of the split DWARF files that couldn't be found and were already warned about.
+    missing: RefCell<HashSet<String>>,
+}
+
+impl SplitDwarf {
+    pub fn new(binary_path: &Path, endian: BinaryEndian) -> SplitDwarf {
+        SplitDwarf {
+            binary_path: binary_path.to_path_buf(),
+            endian: if endian == BinaryEndian::Little {
+                gimli::RunTimeEndian::Little
+            } else {
+                gimli::RunTimeEndian::Big
+            },
+            dwp: OnceCell::new(),
+            dwo_files: RefCell::new(HashMap::new()),
+            missing: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Warns that the split DWARF file `dwo_name` was not found. Many skeleton units can refer to
+    /// the same file so this only warns once for each file.
+    pub fn warn_missing(&self, dwo_name: &str) {
+        if self.missing.borrow_mut().insert(dwo_name.to_string()) {
+            eprintln!("warning: split DWARF file `{}` was not found.", dwo_name);
+        }
+    }
+
+    /// Calls `f` with the split compilation unit that a skeleton unit refers to. The `.dwp`
+    /// package is checked first and then the `.dwo` file itself, which is either relative to the
+    /// skeleton unit's compilation directory or the binary.
+    pub fn with_unit<T, F>(
+        &self,
+        dwo_name: &str,
+        comp_dir: Option<&Path>,
+        dwo_id: u64,
+        f: F,
+    ) -> Option<T>
+    where
+        F: FnOnce(&gimli::Dwarf<SplitReader>, &gimli::Unit<SplitReader>) -> T,
+    {
+        if let Some(package) = self.dwp_package() {
+            if let Some(contributions) =
+                find_dwp_contributions(&package.cu_index, dwo_id, self.endian)
+            {
+                let sections = package.sections.unit_sections(&contributions)?;
+                let (dwarf, unit, _) = sections.split_unit(self.endian)?;
+                return Some(f(&dwarf, &unit));
+            }
+        }
+
+        let mut candidates = Vec::new();
+        if let Some(comp_dir) = comp_dir {
+            candidates.push(comp_dir.join(dwo_name));
+        }
+        if let Some(directory) = self.binary_path.parent() {
+            candidates.push(directory.join(dwo_name));
+        }
+
+        for candidate in candidates.iter() {
+            let sections = if let Some(sections) = self.dwo_file(candidate) {
+                sections
+            } else {
+                continue;
+            };
+
+            match sections.split_unit(self.endian) {
+                Some((dwarf, unit, id)) if id.map(|id| id == dwo_id).unwrap_or(true) => {
+                    return Some(f(&dwarf, &unit));
+                }
+                None => continue,
+                Some(_) => eprintln!(
+                    "warning: ignoring split DWARF file `{}` because its ID does not match the binary.",
+                    candidate.display()
+                ),
+            }
+        }
+
+        None
+    }
+
+    fn dwp_package(&self) -> Option<&DwpPackage> {
+        self.dwp
+            .get_or_init(|| {
+                let mut dwp_path = self.binary_path.clone().into_os_string();
+                dwp_path.push(".dwp");
+                let data = std::fs::read(dwp_path).ok()?;
+                let elf = Elf::parse(&data).ok()?;
+
+                Some(DwpPackage {
+                    cu_index: get_section_by_name(&elf, &data, ".debug_cu_index")
+                        .ok()?
+                        .to_vec(),
+                    sections: SplitSections::load(&elf, &data)?,
+                })
+            })
+            .as_ref()
+    }
+
+    fn dwo_file(&self, path: &Path) -> Option<Rc<SplitSections>> {
+        self.dwo_files
+            .borrow_mut()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let data = std::fs::read(path).ok()?;
+                let elf = Elf::parse(&data).ok()?;
+                SplitSections::load(&elf, &data).map(Rc::new)
+            })
+            .clone()
+    }
+}
+
+/// A `.dwp` package, which contains the split units of every object file of a binary.
+struct DwpPackage {
+    cu_index: Vec<u8>,
+    sections: SplitSections,
+}
+
+/// The (decompressed) sections of a `.dwo` file or `.dwp` package.
+struct SplitSections {
+    info: Vec<u8>,
+    abbrev: Vec<u8>,
+    line: Vec<u8>,
+    str: Vec<u8>,
+    str_offsets: Vec<u8>,
+}
+
+impl SplitSections {
+    fn load(elf: &Elf, data: &[u8]) -> Option<SplitSections> {
+        let section = |name: &str| -> Option<Vec<u8>> {
+            get_debug_section(elf, data, name)
+                .ok()
+                .map(|section| section.into_owned())
+        };
+
+        Some(SplitSections {
+            info: section(".debug_info.dwo")?,
+            abbrev: section(".debug_abbrev.dwo")?,
+            line: section(".debug_line.dwo")?,
+            str: section(".debug_str.dwo")?,
+            str_offsets: section(".debug_str_offsets.dwo")?,
+        })
+    }
+
+    /// Returns the parts of the sections that a single unit of a `.dwp` package contributes.
+    fn unit_sections(&self, contributions: &[(u32, usize, usize)]) -> Option<UnitSections<'_>> {
+        fn contribution<'s>(
+            section: &'s [u8],
+            contributions: &[(u32, usize, usize)],
+            id: u32,
+        ) -> Option<&'s [u8]> {
+            match contributions.iter().find(|c| c.0 == id) {
+                Some(&(_, offset, size)) => section.get(offset..(offset.checked_add(size)?)),
+                None => Some(section),
+            }
+        }
+        let section = |section, id| contribution(section, contributions, id);
+
+        Some(UnitSections {
+            info: section(&self.info, DW_SECT_INFO)?,
+            abbrev: section(&self.abbrev, DW_SECT_ABBREV)?,
+            line: section(&self.line, DW_SECT_LINE)?,
+            str: &self.str,
+            str_offsets: section(&self.str_offsets, DW_SECT_STR_OFFSETS)?,
+        })
+    }
+
+    fn split_unit(
+        &self,
+        endian: gimli::RunTimeEndian,
+    ) -> Option<(
+        gimli::Dwarf<SplitReader<'_>>,
+        gimli::Unit<SplitReader<'_>>,
+        Option<u64>,
+    )> {
+        UnitSections {
+            info: &self.info,
+            abbrev: &self.abbrev,
+            line: &self.line,
+            str: &self.str,
+            str_offsets: &self.str_offsets,
+        }
+        .split_unit(endian)
+    }
+}
+
+/// The sections of a single split compilation unit.
+struct UnitSections<'a> {
+    info: &'a [u8],
+    abbrev: &'a [u8],
+    line: &'a [u8],
+    str: &'a [u8],
+    str_offsets: &'a [u8],
+}
+
+impl<'a> UnitSections<'a> {
+    /// Returns the first compilation unit in the sections along with its ID, which is either in
+    /// the unit header (DWARF 5) or in the `DW_AT_GNU_dwo_id` attribute.
+    fn split_unit(
+        &self,
+        endian: gimli::RunTimeEndian,
+    ) -> Option<(
+        gimli::Dwarf<SplitReader<'a>>,
+        gimli::Unit<SplitReader<'a>>,
+        Option<u64>,
+    )> {
+        let loader = |section: gimli::SectionId| -> Result<_, gimli::Error> {
+            let data = match section {
+                gimli::SectionId::DebugInfo => self.info,
+                gimli::SectionId::DebugAbbrev => self.abbrev,
+                gimli::SectionId::DebugLine => self.line,
+                gimli::SectionId::DebugStr => self.str,
+                gimli::SectionId::DebugStrOffsets => self.str_offsets,
+                _ => &[],
+            };
+            Ok(gimli::EndianSlice::new(data, endian))
+        };
+        let sup_loader =
+            |_section: gimli::SectionId| Ok(gimli::EndianSlice::new(&[] as &[u8], endian));
+        let mut dwarf = gimli::Dwarf::load(loader, sup_loader).ok()?;
+        dwarf.file_type = gimli::DwarfFileType::Dwo;
+
+        let header = dwarf.units().next().ok()??;
+        let mut unit = dwarf.unit(header).ok()?;
+
+        // Split units don't have a `DW_AT_stmt_list` because their line program is always at the
+        // start of their contribution to `.debug_line.dwo`.
+        if unit.line_program.is_none() && !self.line.is_empty() {
+            unit.line_program = dwarf
+                .debug_line
+                .program(
+                    gimli::DebugLineOffset(0),
+                    unit.header.address_size(),
+                    unit.comp_dir,
+                    unit.name,
+                )
+                .ok();
+        }
+
+        let dwo_id = if let gimli::UnitType::SplitCompilation(id) = unit.header.type_() {
+            Some(id.0)
+        } else {
+            let mut entries = unit.entries();
+            let (_, root) = entries.next_dfs().ok()??;
+            root.attr_value(gimli::DW_AT_GNU_dwo_id)
+                .ok()?
+                .and_then(|id| id.udata_value())
+        };
+
+        Some((dwarf, unit, dwo_id))
+    }
+}
+
+/// Finds the unit with the given ID in the `.debug_cu_index` of a `.dwp` package and returns the
+/// section identifier, offset and size of each of its contributions.
+fn find_dwp_contributions(
+    cu_index: &[u8],
+    dwo_id: u64,
+    endian: gimli::RunTimeEndian,
+) -> Option<Vec<(u32, usize, usize)>> {
+    let mut header = gimli::EndianSlice::new(cu_index, endian);
+
+    // Version 2 is the GNU extension, version 5 uses a 2 byte version followed by padding.
+    let version = header.read_u32().ok()?;
+    if version != 2 && version != 5 && (version >> 16) != 5 {
+        return None;
+    }
+    let section_count = header.read_u32().ok()? as usize;
+    let unit_count = header.read_u32().ok()? as usize;
+    let slot_count = header.read_u32().ok()? as usize;
+
+    let mut signatures = header;
+    let mut indices = header;
+    indices.skip(slot_count * 8).ok()?;
+
+    let mut row = None;
+    for _ in 0..slot_count {
+        let signature = signatures.read_u64().ok()?;
+        let index = indices.read_u32().ok()? as usize;
+        if index != 0 && signature == dwo_id {
+            row = Some(index - 1);
+            break;
+        }
+    }
+    let row = row?;
+    if row >= unit_count {
+        return None;
+    }
+
+    let mut section_ids = header;
+    section_ids.skip(slot_count * 12).ok()?;
+    let mut offsets = section_ids;
+    offsets
+        .skip(section_count * 4 + row * section_count * 4)
+        .ok()?;
+    let mut sizes = section_ids;
+    sizes
+        .skip(section_count * 4 + unit_count * section_count * 4 + row * section_count * 4)
+        .ok()?;
+
+    let mut contributions = Vec::new();
+    for _ in 0..section_count {
+        contributions.push((
+            section_ids.read_u32().ok()?,
+            offsets.read_u32().ok()? as usize,
+            sizes.read_u32().ok()? as usize,
+        ));
+    }
+    Some(contributions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `.debug_cu_index` with two units in four hash slots, which contribute to the
+    /// info, abbrev and str_offsets sections.
+    fn cu_index(version: &[u8], endian: gimli::RunTimeEndian) -> Vec<u8> {
+        let u32_bytes = |value: u32| match endian {
+            gimli::RunTimeEndian::Little => value.to_le_bytes(),
+            gimli::RunTimeEndian::Big => value.to_be_bytes(),
+        };
+        let u64_bytes = |value: u64| match endian {
+            gimli::RunTimeEndian::Little => value.to_le_bytes(),
+            gimli::RunTimeEndian::Big => value.to_be_bytes(),
+        };
+
+        let mut data = version.to_vec();
+        for &value in [3, 2, 4].iter() {
+            data.extend_from_slice(&u32_bytes(value));
+        }
+        for &signature in [0, 0xAAAA, 0, 0xBBBB].iter() {
+            data.extend_from_slice(&u64_bytes(signature));
+        }
+        for &index in [0, 2, 0, 1].iter() {
+            data.extend_from_slice(&u32_bytes(index));
+        }
+        let section_ids = [DW_SECT_INFO, DW_SECT_ABBREV, DW_SECT_STR_OFFSETS];
+        let offsets = [0, 0, 0, 0x40, 0x10, 0x20];
+        let sizes = [0x40, 0x10, 0x20, 0x30, 0x18, 0x28];
+        for &value in section_ids.iter().chain(offsets.iter()).chain(sizes.iter()) {
+            data.extend_from_slice(&u32_bytes(value));
+        }
+        data
+    }
+
+    #[test]
+    fn dwp_contributions_of_each_unit() {
+        let endian = gimli::RunTimeEndian::Little;
+        let index = cu_index(&[2, 0, 0, 0], endian);
+
+        assert_eq!(
+            find_dwp_contributions(&index, 0xBBBB, endian),
+            Some(vec![(1, 0, 0x40), (3, 0, 0x10), (6, 0, 0x20)])
+        );
+        assert_eq!(
+            find_dwp_contributions(&index, 0xAAAA, endian),
+            Some(vec![(1, 0x40, 0x30), (3, 0x10, 0x18), (6, 0x20, 0x28)])
+        );
+    }
+
+    #[test]
+    fn dwp_contributions_of_dwarf_5_index() {
+        let index = cu_index(&[5, 0, 0, 0], gimli::RunTimeEndian::Little);
+        assert!(find_dwp_contributions(&index, 0xAAAA, gimli::RunTimeEndian::Little).is_some());
+
+        // The 2 byte version is followed by 2 bytes of padding.
+        let index = cu_index(&[0, 5, 0, 0], gimli::RunTimeEndian::Big);
+        assert!(find_dwp_contributions(&index, 0xAAAA, gimli::RunTimeEndian::Big).is_some());
+    }
+
+    #[test]
+    fn dwp_contributions_of_unknown_unit() {
+        let endian = gimli::RunTimeEndian::Little;
+        let index = cu_index(&[2, 0, 0, 0], endian);
+
+        assert_eq!(find_dwp_contributions(&index, 0xCCCC, endian), None);
+        // Empty slots have a signature of 0 and must not match.
+        assert_eq!(find_dwp_contributions(&index, 0, endian), None);
+    }
+
+    #[test]
+    fn dwp_contributions_of_invalid_index() {
+        let endian = gimli::RunTimeEndian::Little;
+        let index = cu_index(&[2, 0, 0, 0], endian);
+
+        assert_eq!(find_dwp_contributions(&[], 0xAAAA, endian), None);
+        assert_eq!(
+            find_dwp_contributions(&cu_index(&[4, 0, 0, 0], endian), 0xAAAA, endian),
+            None
+        );
+        assert_eq!(
+            find_dwp_contributions(&index[..index.len() - 4], 0xAAAA, endian),
+            None
+        );
+
+        // A row index past the number of units.
+        let mut index = index;
+        index[16 + 4 * 8 + 4..16 + 4 * 8 + 8].copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(find_dwp_contributions(&index, 0xAAAA, endian), None);
+    }
+}
@@ -1,5 +1,8 @@
+use crate::arch::AsmSyntax;
+use crate::disasm::{MatchMode, MatchName};
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use std::borrow::Cow;
+use std::ops::Range;
 use std::path::PathBuf;
 
 #[derive(Debug)]
@@ -12,6 +15,10 @@ pub enum CliCommand {
 pub struct ListArgs {
     pub binary_path: Option<PathBuf>,
     pub needle: String,
+    pub arch: Option<String>,
+    pub debug_file: Option<PathBuf>,
+    pub matching: MatchArgs,
+    pub show_hashes: bool,
 
     pub cargo: CargoArgs,
 }
@@ -19,7 +26,14 @@ pub struct ListArgs {
 #[derive(Debug)]
 pub struct DisasmArgs {
     pub binary_path: Option<PathBuf>,
-    pub needle: String,
+    pub target: DisasmTarget,
+    pub context: usize,
+    pub arch: Option<String>,
+    pub debug_file: Option<PathBuf>,
+    pub index: Option<usize>,
+    pub all: bool,
+    pub matching: MatchArgs,
+    pub show_hashes: bool,
     pub show_jumps: bool,
     pub show_bytes: bool,
     pub show_addrs: bool,
@@ -27,9 +41,32 @@ pub struct DisasmArgs {
     pub disable_color: bool,
     pub absolute_source_path: bool,
     pub source_root: Option<PathBuf>,
+    pub syntax: AsmSyntax,
     pub cargo: CargoArgs,
 }
 
+/// What the `disasm` command disassembles.
+#[derive(Debug)]
+pub enum DisasmTarget {
+    /// The symbols whose names match a search string.
+    Search(String),
+
+    /// The symbol that contains an address.
+    Address(u64),
+
+    /// A range of addresses, which doesn't have to be covered by a symbol.
+    Range(Range<u64>),
+
+    /// The symbols that contain code for a line of a source file.
+    Line(PathBuf, u32),
+}
+
+#[derive(Debug)]
+pub struct MatchArgs {
+    pub mode: MatchMode,
+    pub name: MatchName,
+}
+
 #[derive(Debug)]
 pub struct CargoArgs {
     pub manifest_path: Option<PathBuf>,
@@ -53,6 +90,90 @@ pub fn parse_cli_args() -> CliCommand {
                         .value_name("BINARY")
                         .help("Path of a binary to disassemble and search for symbols in."),
                 )
+                .arg(
+                    Arg::with_name("arch")
+                        .long("arch")
+                        .takes_value(true)
+                        .value_name("ARCH")
+                        .help("The architecture (e.g. x86_64 or arm64) to use from a universal Mach-O binary."),
+                )
+                .arg(
+                    Arg::with_name("index")
+                        .long("index")
+                        .takes_value(true)
+                        .value_name("N")
+                        .validator(|n| n.parse::<usize>().map(|_| ()).map_err(|err| err.to_string()))
+                        .help("Disassemble the N-th symbol (starting at 0) when the search string matches several symbols."),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .conflicts_with("index")
+                        .help("Disassemble every symbol that matches the search string."),
+                )
+                .arg(
+                    Arg::with_name("addr")
+                        .long("addr")
+                        .takes_value(true)
+                        .value_name("ADDRESS")
+                        .conflicts_with_all(&[
+                            "SEARCH",
+                            "range",
+                            "index",
+                            "all",
+                            "exact",
+                            "regex",
+                            "glob",
+                            "match-name",
+                        ])
+                        .validator(|addr| parse_addr(&addr).map(|_| ()))
+                        .help("Disassemble the symbol that contains a (hexadecimal) address and highlight the instruction at that address."),
+                )
+                .arg(
+                    Arg::with_name("range")
+                        .long("range")
+                        .takes_value(true)
+                        .value_name("START..END")
+                        .conflicts_with_all(&[
+                            "SEARCH",
+                            "index",
+                            "all",
+                            "exact",
+                            "regex",
+                            "glob",
+                            "match-name",
+                        ])
+                        .validator(|range| parse_addr_range(&range).map(|_| ()))
+                        .help("Disassemble the code in a range of (hexadecimal) addresses, even if it isn't part of a symbol."),
+                )
+                .arg(
+                    Arg::with_name("line")
+                        .long("line")
+                        .takes_value(true)
+                        .value_name("FILE:LINE")
+                        .conflicts_with_all(&[
+                            "SEARCH",
+                            "addr",
+                            "range",
+                            "index",
+                            "all",
+                            "exact",
+                            "regex",
+                            "glob",
+                            "match-name",
+                        ])
+                        .validator(|line| parse_source_line(&line).map(|_| ()))
+                        .help("Disassemble every symbol that contains code for a line of a source file (e.g. src/main.rs:42) and highlight that code."),
+                )
+                .arg(
+                    Arg::with_name("context")
+                        .long("context")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("8")
+                        .validator(|n| n.parse::<usize>().map(|_| ()).map_err(|err| err.to_string()))
+                        .help("The number of instructions shown before and after the instruction at the address passed to `--addr`."),
+                )
                 .arg(
                     Arg::with_name("jumps")
                         .short("J")
@@ -76,6 +197,14 @@ pub fn parse_cli_args() -> CliCommand {
                         .long("source-abs")
                         .help("Prefer absolute paths from debug information (if they are available) for creating source line maps."),
                 )
+                .arg(
+                    Arg::with_name("debug-file")
+                        .long("debug-file")
+                        .visible_alias("pdb")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Path of a separate file containing debug information for the binary (e.g. a .debug file, a .dSYM bundle or a PDB)."),
+                )
                 .arg(
                     Arg::with_name("source-root")
                         .long("source-root")
@@ -91,12 +220,52 @@ pub fn parse_cli_args() -> CliCommand {
                         .long("no-addr")
                         .help("Don't show the address of instructions."),
                 )
+                .arg(
+                    Arg::with_name("syntax")
+                        .long("syntax")
+                        .takes_value(true)
+                        .value_name("SYNTAX")
+                        .possible_values(&["intel", "att", "masm"])
+                        .default_value("intel")
+                        .help("The assembly syntax used for x86 and x86-64 instructions."),
+                )
                 .arg(
                     Arg::with_name("no-color")
                         .short("C")
                         .long("no-color")
                         .help("Disable color in disassembly output."),
                 )
+                .arg(
+                    Arg::with_name("exact")
+                        .long("exact")
+                        .conflicts_with_all(&["regex", "glob"])
+                        .help("Only match symbol names that are exactly the same as the search string."),
+                )
+                .arg(
+                    Arg::with_name("regex")
+                        .long("regex")
+                        .conflicts_with("glob")
+                        .help("Use the search string as a regular expression."),
+                )
+                .arg(
+                    Arg::with_name("glob")
+                        .long("glob")
+                        .help("Use the search string as a glob pattern (e.g. `*Vec<u8>::push`)."),
+                )
+                .arg(
+                    Arg::with_name("match-name")
+                        .long("match-name")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .possible_values(&["mangled", "demangled", "short"])
+                        .default_value("demangled")
+                        .help("The form of the symbol names that the search string is matched against."),
+                )
+                .arg(
+                    Arg::with_name("hashes")
+                        .long("hashes")
+                        .help("Show the `::h<hash>` suffix of Rust symbol names and the `[<hash>]` disambiguators of their crates."),
+                )
                 .arg(
                     Arg::with_name("release")
                         .long("release")
@@ -111,7 +280,7 @@ pub fn parse_cli_args() -> CliCommand {
                 .arg(
                     Arg::with_name("SEARCH")
                         .help("The string to search for in a symbol name")
-                        .required(true)
+                        .required_unless_one(&["addr", "range", "line"])
                         .index(1),
                 ),
         )
@@ -126,6 +295,52 @@ pub fn parse_cli_args() -> CliCommand {
                         .value_name("BINARY")
                         .help("Path of a binary to disassemble and search for symbols in."),
                 )
+                .arg(
+                    Arg::with_name("arch")
+                        .long("arch")
+                        .takes_value(true)
+                        .value_name("ARCH")
+                        .help("The architecture (e.g. x86_64 or arm64) to use from a universal Mach-O binary."),
+                )
+                .arg(
+                    Arg::with_name("debug-file")
+                        .long("debug-file")
+                        .visible_alias("pdb")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Path of a separate file containing debug information for the binary (e.g. a .debug file, a .dSYM bundle or a PDB)."),
+                )
+                .arg(
+                    Arg::with_name("exact")
+                        .long("exact")
+                        .conflicts_with_all(&["regex", "glob"])
+                        .help("Only match symbol names that are exactly the same as the search string."),
+                )
+                .arg(
+                    Arg::with_name("regex")
+                        .long("regex")
+                        .conflicts_with("glob")
+                        .help("Use the search string as a regular expression."),
+                )
+                .arg(
+                    Arg::with_name("glob")
+                        .long("glob")
+                        .help("Use the search string as a glob pattern (e.g. `*Vec<u8>::push`)."),
+                )
+                .arg(
+                    Arg::with_name("match-name")
+                        .long("match-name")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .possible_values(&["mangled", "demangled", "short"])
+                        .default_value("demangled")
+                        .help("The form of the symbol names that the search string is matched against."),
+                )
+                .arg(
+                    Arg::with_name("hashes")
+                        .long("hashes")
+                        .help("Show the `::h<hash>` suffix of Rust symbol names and the `[<hash>]` disambiguators of their crates."),
+                )
                 .arg(
                     Arg::with_name("release")
                         .long("release")
@@ -148,13 +363,36 @@ pub fn parse_cli_args() -> CliCommand {
 
     if let Some(matches) = matches.subcommand_matches("disasm") {
         let binary_path = matches.value_of("binary").map(path_arg);
-        let needle = matches.value_of("SEARCH").unwrap().to_string();
+        let target = if let Some(addr) = matches.value_of("addr") {
+            DisasmTarget::Address(parse_addr(addr).unwrap())
+        } else if let Some(range) = matches.value_of("range") {
+            DisasmTarget::Range(parse_addr_range(range).unwrap())
+        } else if let Some(line) = matches.value_of("line") {
+            let (file, line) = parse_source_line(line).unwrap();
+            DisasmTarget::Line(file, line)
+        } else {
+            DisasmTarget::Search(matches.value_of("SEARCH").unwrap().to_string())
+        };
+        let context = matches
+            .value_of("context")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(8);
+        let arch = matches.value_of("arch").map(String::from);
         let source_root = matches.value_of("source-root").map(path_arg);
+        let debug_file = matches.value_of("debug-file").map(path_arg);
+        let index = matches.value_of("index").and_then(|n| n.parse().ok());
+        let syntax = match matches.value_of("syntax") {
+            Some("att") => AsmSyntax::Att,
+            Some("masm") => AsmSyntax::Masm,
+            _ => AsmSyntax::Intel,
+        };
         let cargo = get_cargo_args(&matches);
 
         return CliCommand::Disasm(DisasmArgs {
             binary_path,
-            needle,
+            target,
+            context,
+            arch,
             cargo,
 
             show_addrs: !matches.is_present("no-addr"),
@@ -164,17 +402,29 @@ pub fn parse_cli_args() -> CliCommand {
             disable_color: matches.is_present("no-color"),
             absolute_source_path: matches.is_present("source-path-absolute"),
             source_root,
+            debug_file,
+            index,
+            all: matches.is_present("all"),
+            matching: get_match_args(matches),
+            show_hashes: matches.is_present("hashes"),
+            syntax,
         });
     }
 
     if let Some(matches) = matches.subcommand_matches("list") {
         let binary_path = matches.value_of("binary").map(path_arg);
         let needle = matches.value_of("FILTER").unwrap().to_string();
+        let arch = matches.value_of("arch").map(String::from);
+        let debug_file = matches.value_of("debug-file").map(path_arg);
         let cargo = get_cargo_args(&matches);
 
         return CliCommand::List(ListArgs {
             binary_path,
             needle,
+            arch,
+            debug_file,
+            matching: get_match_args(matches),
+            show_hashes: matches.is_present("hashes"),
             cargo,
         });
     }
@@ -182,6 +432,26 @@ pub fn parse_cli_args() -> CliCommand {
     std::process::exit(1);
 }
 
+fn get_match_args(matches: &ArgMatches) -> MatchArgs {
+    let mode = if matches.is_present("exact") {
+        MatchMode::Exact
+    } else if matches.is_present("regex") {
+        MatchMode::Regex
+    } else if matches.is_present("glob") {
+        MatchMode::Glob
+    } else {
+        MatchMode::Fuzzy
+    };
+
+    let name = match matches.value_of("match-name") {
+        Some("mangled") => MatchName::Mangled,
+        Some("short") => MatchName::Short,
+        _ => MatchName::Demangled,
+    };
+
+    MatchArgs { mode, name }
+}
+
 fn get_cargo_args(matches: &ArgMatches) -> CargoArgs {
     let profile = if matches.is_present("release") {
         Some(Cow::from("release"))
@@ -197,6 +467,72 @@ fn get_cargo_args(matches: &ArgMatches) -> CargoArgs {
     }
 }
 
+/// Parses a hexadecimal address with or without a `0x` prefix.
+fn parse_addr(arg_str: &str) -> Result<u64, String> {
+    let digits = arg_str
+        .strip_prefix("0x")
+        .or_else(|| arg_str.strip_prefix("0X"))
+        .unwrap_or(arg_str);
+    u64::from_str_radix(digits, 16).map_err(|err| format!("invalid address `{}`: {}", arg_str, err))
+}
+
+/// Parses a range of addresses in the form `start..end`.
+fn parse_addr_range(arg_str: &str) -> Result<Range<u64>, String> {
+    let mut parts = arg_str.splitn(2, "..");
+    let start = parse_addr(parts.next().unwrap_or(""))?;
+    let end = parts
+        .next()
+        .ok_or_else(|| format!("invalid address range `{}`, expected START..END", arg_str))
+        .and_then(parse_addr)?;
+
+    if start >= end {
+        return Err(format!(
+            "invalid address range `{}`, the range is empty",
+            arg_str
+        ));
+    }
+    Ok(start..end)
+}
+
+/// Parses a source location in the form `file:line`.
+fn parse_source_line(arg_str: &str) -> Result<(PathBuf, u32), String> {
+    let mut parts = arg_str.rsplitn(2, ':');
+    let line = parts.next().and_then(|line| line.parse::<u32>().ok());
+    match (parts.next(), line) {
+        (Some(file), Some(line)) if !file.is_empty() => Ok((path_arg(file), line)),
+        _ => Err(format!(
+            "invalid source location `{}`, expected FILE:LINE",
+            arg_str
+        )),
+    }
+}
+
 fn path_arg(arg_str: &str) -> PathBuf {
     PathBuf::from(&shellexpand::tilde(arg_str) as &str)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_addr_ranges() {
+        assert_eq!(parse_addr_range("1000..1020"), Ok(0x1000..0x1020));
+        assert_eq!(parse_addr_range("0x10..0X2f"), Ok(0x10..0x2f));
+    }
+
+    #[test]
+    fn parse_addr_range_rejects_empty_ranges() {
+        assert!(parse_addr_range("10..10").is_err());
+        assert!(parse_addr_range("20..10").is_err());
+    }
+
+    #[test]
+    fn parse_addr_range_rejects_malformed_ranges() {
+        assert!(parse_addr_range("1000").is_err());
+        assert!(parse_addr_range("..1000").is_err());
+        assert!(parse_addr_range("1000..").is_err());
+        assert!(parse_addr_range("1000...2000").is_err());
+        assert!(parse_addr_range("1000..zz").is_err());
+    }
+}
@@ -1,6 +1,8 @@
-use crate::arch::InnerJumpTable;
+use crate::arch::{find_address_in_operands, InnerJumpTable};
+use crate::binary::{Binary, BinaryArch, Relocation};
 use crate::disasm::{DisasmConfig, DisasmContext};
 use capstone::Insn;
+use std::borrow::Cow;
 use std::io::Write;
 
 /*
@@ -81,17 +83,20 @@ pub fn write_arrow_pieces_for_line(
     Ok(())
 }
 
+/// Creates a buffer of arrow pieces for `height` lines of output. `instr_lines` maps the
+/// instruction indices used by the jump table to the output line of each instruction.
 pub fn create_jump_arrows_buffer(
     width: usize,
     height: usize,
+    instr_lines: &[usize],
     jumps: &InnerJumpTable,
 ) -> Vec<ArrowPiece> {
     let mut pieces: Vec<ArrowPiece> = Vec::with_capacity(width * height);
     pieces.resize(width * height, ArrowPiece::None);
 
     for jump in jumps.iter() {
-        let from_y = jump.source;
-        let to_y = jump.target;
+        let from_y = instr_lines[jump.source];
+        let to_y = instr_lines[jump.target];
 
         if from_y == to_y {
             continue;
@@ -223,7 +228,7 @@ pub struct OutputMeasure {
 }
 
 pub fn measure<'i>(
-    instrs: &'i [Insn<'i>],
+    instrs: &'i [&'i Insn<'i>],
     config: &DisasmConfig,
     context: &DisasmContext,
 ) -> OutputMeasure {
@@ -250,20 +255,100 @@ pub fn measure<'i>(
                 instr.mnemonic().map(|m| m.len()).unwrap_or(0),
             );
 
-            if let (true, Some(patch)) = (config.display_patches, context.op_patches.get(idx)) {
-                measure.operands_width = max(measure.operands_width, patch.len());
-            } else {
-                measure.operands_width = max(
-                    measure.operands_width,
-                    instr.op_str().map(|ops| ops.len()).unwrap_or(0),
-                );
-            }
+            measure.operands_width =
+                max(measure.operands_width, operands(instr, idx, context).len());
+        }
+    }
+
+    for chunk in context.data_chunks.iter() {
+        if config.display_address {
+            measure.address_width = max(measure.address_width, addr_len(chunk.addr));
+        }
+
+        if config.display_bytes {
+            measure.bytes_width = max(measure.bytes_width, hex_len(chunk.bytes));
+        }
+
+        if config.display_instr {
+            measure.mnemonic_width = max(measure.mnemonic_width, chunk.directive().len());
+            measure.operands_width = max(measure.operands_width, 2 + chunk.bytes.len() * 2);
         }
     }
 
     measure
 }
 
+/// Returns the operands of an instruction as they are displayed, with operand patches applied if
+/// they are enabled.
+pub fn operands<'i>(instr: &'i Insn<'i>, idx: usize, context: &DisasmContext) -> Cow<'i, str> {
+    let op_str = instr.op_str().unwrap_or("");
+
+    let instr_end = instr.address() + instr.bytes().len() as u64;
+    if let Some(reloc) = context
+        .binary
+        .relocations_in(instr.address()..instr_end)
+        .first()
+    {
+        return Cow::from(relocation_operands(instr, op_str, reloc, context.binary));
+    }
+
+    let config = &context.config;
+    if config.display_patches {
+        if let Some(patched) = context
+            .op_patches
+            .patch_operands(idx, op_str, config.display_hashes)
+        {
+            return Cow::from(patched);
+        }
+    }
+
+    Cow::from(op_str)
+}
+
+/// Replaces the unrelocated target of an instruction with a description of the relocation that
+/// fills it in, e.g. `call R_X86_64_PLT32 core::fmt::write`. Operands that don't contain a
+/// target address (like a RIP relative memory operand) get the relocation appended instead.
+fn relocation_operands(
+    instr: &Insn<'_>,
+    op_str: &str,
+    reloc: &Relocation<'_>,
+    binary: &Binary<'_>,
+) -> String {
+    let instr_end = instr.address() + instr.bytes().len() as u64;
+
+    // x86 PC relative relocations are relative to the address of the field that they patch
+    // while the CPU adds the displacement to the end of the instruction, which is what the
+    // addend compensates for.
+    let mut addend = reloc.addend;
+    if reloc.pc_relative && matches!(binary.arch, BinaryArch::X86 | BinaryArch::AMD64) {
+        addend += (instr_end - reloc.addr) as i64;
+    }
+
+    let description = match addend {
+        0 => format!("{} {}", reloc.kind, reloc.symbol),
+        a if a < 0 => format!("{} {}-0x{:x}", reloc.kind, reloc.symbol, -a),
+        a => format!("{} {}+0x{:x}", reloc.kind, reloc.symbol, a),
+    };
+
+    // Unrelocated branches point at either themselves or the next instruction, depending on
+    // what the architecture makes them relative to.
+    let target = find_address_in_operands(op_str, instr_end)
+        .or_else(|| find_address_in_operands(op_str, instr.address()));
+
+    if let Some(range) = target {
+        format!(
+            "{}{}{}",
+            &op_str[..range.start],
+            description,
+            &op_str[range.end..]
+        )
+    } else if op_str.is_empty() {
+        description
+    } else {
+        format!("{} <{}>", op_str, description)
+    }
+}
+
 /// Returns the number of digits required to display an offset in decimal.
 /// This assumes that all of the digits are packed together with no spaces or punctuation.
 pub fn off_len(mut off: usize) -> usize {
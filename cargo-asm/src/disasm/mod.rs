@@ -1,36 +1,118 @@
 pub mod format;
 
-use crate::arch::{analyze_instructions, InnerJumpTable, OperandPatches};
-use crate::binary::{Binary, FileResolveStrategy, LineMappings, Symbol};
-use crate::errors::WCapstoneError;
+use crate::arch::{
+    analyze_instructions, create_capstone, create_thumb_capstone, AsmSyntax, InnerJumpTable,
+    OperandPatches,
+};
+use crate::binary::{
+    Binary, BinaryArch, BinaryEndian, FileResolveStrategy, LineMappings, MappingKind, Symbol,
+};
+use crate::errors::{CargoAsmError, WCapstoneError};
 use crate::line_cache::FileLineCache;
-use capstone::prelude::*;
 use capstone::Insn;
 use std::io::Write;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use termcolor::{Color, ColorSpec, WriteColor};
 
+/// How the search string given on the command line is compared against symbol names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Case-insensitive, in-order matching of the identifiers in the search string. Any other
+    /// characters in the search string are ignored.
+    Fuzzy,
+
+    /// The symbol name must be exactly the same as the search string.
+    Exact,
+
+    /// The search string is a regular expression that must match somewhere in the symbol name.
+    Regex,
+
+    /// The search string is a glob pattern (e.g. `alloc::vec::Vec<u8>::*`) that must match the
+    /// whole symbol name.
+    Glob,
+}
+
+/// The name of a symbol that is compared against the search string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchName {
+    /// The original and possibly mangled name.
+    Mangled,
+    Demangled,
+    Short,
+}
+
 pub struct SymbolMatcher<'a> {
     original_needle: &'a str,
-    tokens: Vec<&'a str>,
+    pattern: MatchPattern<'a>,
+    name: MatchName,
+    hashes: bool,
+}
+
+enum MatchPattern<'a> {
+    Tokens(Vec<&'a str>),
+    Exact,
+    Regex(regex::Regex),
+    Glob(glob::Pattern),
 }
 
 impl<'a> SymbolMatcher<'a> {
-    pub fn new(needle: &'a str) -> SymbolMatcher<'a> {
-        let mut tokens = Vec::new();
-        Self::tokenize(needle, &mut tokens);
-        SymbolMatcher {
+    pub fn new(
+        needle: &'a str,
+        mode: MatchMode,
+        name: MatchName,
+        hashes: bool,
+    ) -> Result<SymbolMatcher<'a>, CargoAsmError> {
+        let invalid_pattern = |err: &dyn std::fmt::Display| {
+            CargoAsmError::InvalidSearchPattern(needle.to_string(), err.to_string())
+        };
+
+        let pattern = match mode {
+            MatchMode::Fuzzy => {
+                let mut tokens = Vec::new();
+                Self::tokenize(needle, &mut tokens);
+                MatchPattern::Tokens(tokens)
+            }
+            MatchMode::Exact => MatchPattern::Exact,
+            MatchMode::Regex => {
+                MatchPattern::Regex(regex::Regex::new(needle).map_err(|err| invalid_pattern(&err))?)
+            }
+            MatchMode::Glob => {
+                MatchPattern::Glob(glob::Pattern::new(needle).map_err(|err| invalid_pattern(&err))?)
+            }
+        };
+
+        Ok(SymbolMatcher {
             original_needle: needle,
-            tokens,
-        }
+            pattern,
+            name,
+            hashes,
+        })
     }
 
     pub fn needle(&self) -> &str {
         self.original_needle
     }
 
-    pub fn matches(&self, mut name: &str) -> bool {
-        for token in self.tokens.iter() {
+    pub fn matches_symbol(&self, symbol: &Symbol) -> bool {
+        match self.name {
+            MatchName::Mangled => self.matches(&symbol.original_name),
+            MatchName::Demangled => self.matches(&symbol.display_name(self.hashes)),
+            MatchName::Short => self.matches(&symbol.display_short_name(self.hashes)),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self.pattern {
+            MatchPattern::Tokens(ref tokens) => Self::matches_tokens(tokens, name),
+            MatchPattern::Exact => name == self.original_needle,
+            MatchPattern::Regex(ref regex) => regex.is_match(name),
+            MatchPattern::Glob(ref glob) => glob.matches(name),
+        }
+    }
+
+    fn matches_tokens(tokens: &[&str], mut name: &str) -> bool {
+        for token in tokens.iter() {
             if let Some((_found_idx, found_end_idx)) = Self::find_ignore_case(name, token) {
                 name = &name[found_end_idx..];
             } else {
@@ -99,6 +181,7 @@ impl<'a> SymbolMatcher<'a> {
 pub struct DisasmConfig {
     pub display_address: bool,
     pub display_patches: bool,
+    pub display_hashes: bool,
     pub display_bytes: bool,
     pub display_jumps: bool,
     pub display_instr: bool,
@@ -108,6 +191,18 @@ pub struct DisasmConfig {
     pub load_debug_info: bool,
     pub display_length: bool,
     pub display_instr_count: bool,
+    pub syntax: AsmSyntax,
+
+    /// Separate file with debug information for the binary (a `.debug` file, a dSYM bundle or a
+    /// PDB) that is used instead of searching for one.
+    pub debug_file: Option<PathBuf>,
+
+    /// Instructions that overlap one of these address ranges are highlighted.
+    pub highlight: Vec<Range<u64>>,
+
+    /// If set, only this many instructions before and after each highlighted instruction are
+    /// displayed instead of the whole symbol.
+    pub highlight_context: Option<usize>,
 }
 
 pub fn disassemble<'a, Out: Write + WriteColor>(
@@ -117,23 +212,48 @@ pub fn disassemble<'a, Out: Write + WriteColor>(
 ) -> anyhow::Result<()> {
     context.clear();
 
-    let symbol_code = &context.binary.data()[symbol.offset_range()];
+    let binary = context.binary;
+    let cs = create_capstone(
+        binary.arch,
+        binary.bits,
+        binary.endian,
+        context.config.syntax,
+    )?;
+    let cs_thumb = if binary.arch == BinaryArch::ARM {
+        Some(create_thumb_capstone(binary.endian)?)
+    } else {
+        None
+    };
+
+    let symbol_code = &binary.data()[symbol.offset_range()];
 
-    // FIXME support other ISAs
-    let cs = Capstone::new()
-        .x86()
-        .mode(arch::x86::ArchMode::Mode64)
-        .syntax(arch::x86::ArchSyntax::Intel)
-        .detail(true)
-        .build()
-        .map_err(WCapstoneError)?;
-    let instrs = cs
-        .disasm_all(symbol_code, symbol.addr)
-        .map_err(WCapstoneError)?;
+    let mut decoded = Vec::new();
+    for (region, kind) in code_regions(binary, symbol) {
+        let region_code = &symbol_code
+            [((region.start - symbol.addr) as usize)..((region.end - symbol.addr) as usize)];
+
+        let region_cs = match kind {
+            MappingKind::Code => &cs,
+            MappingKind::Thumb => cs_thumb.as_ref().unwrap_or(&cs),
+            MappingKind::Data => {
+                split_data_chunks(region.start, region_code, &mut context.data_chunks);
+                continue;
+            }
+        };
+
+        decoded.push(
+            region_cs
+                .disasm_all(region_code, region.start)
+                .map_err(WCapstoneError)?,
+        );
+    }
+    let instrs = decoded
+        .iter()
+        .flat_map(|instrs| instrs.iter())
+        .collect::<Vec<&Insn>>();
 
     analyze_instructions(
-        &context.binary.symbols,
-        context.binary.arch,
+        binary,
         &cs,
         &instrs,
         &mut context.jumps,
@@ -143,9 +263,92 @@ pub fn disassemble<'a, Out: Write + WriteColor>(
     write_disasm_output(symbol, &instrs, context, output)
 }
 
+/// Splits a symbol into address ranges that have to be decoded differently according to the
+/// binary's mapping symbols. Binaries without mapping symbols produce a single range of code.
+fn code_regions(binary: &Binary, symbol: &Symbol) -> Vec<(Range<u64>, MappingKind)> {
+    let symbol_start = symbol.addr;
+    let symbol_end = symbol.addr + symbol.size as u64;
+
+    // The mapping in effect at the start of the symbol is the last one at or before it.
+    let first_mapping = match binary.mappings.binary_search_by_key(&symbol_start, |m| m.0) {
+        Ok(idx) => Some(idx),
+        Err(0) => None,
+        Err(idx) => Some(idx - 1),
+    };
+
+    let mut regions = Vec::new();
+    let mut region_start = symbol_start;
+    let mut region_kind = first_mapping
+        .map(|idx| binary.mappings[idx].1)
+        .unwrap_or(MappingKind::Code);
+
+    for &(addr, kind) in binary.mappings[first_mapping.map(|idx| idx + 1).unwrap_or(0)..]
+        .iter()
+        .take_while(|m| m.0 < symbol_end)
+    {
+        if kind != region_kind {
+            if addr > region_start {
+                regions.push((region_start..addr, region_kind));
+            }
+            region_start = addr;
+            region_kind = kind;
+        }
+    }
+    regions.push((region_start..symbol_end, region_kind));
+
+    regions
+}
+
+/// Splits a region of data into naturally aligned words, halfwords and bytes.
+fn split_data_chunks<'a>(mut addr: u64, mut bytes: &'a [u8], chunks: &mut Vec<DataChunk<'a>>) {
+    while !bytes.is_empty() {
+        let len = if addr % 4 == 0 && bytes.len() >= 4 {
+            4
+        } else if addr % 2 == 0 && bytes.len() >= 2 {
+            2
+        } else {
+            1
+        };
+
+        chunks.push(DataChunk {
+            addr,
+            bytes: &bytes[..len],
+        });
+        addr += len as u64;
+        bytes = &bytes[len..];
+    }
+}
+
+/// Bytes inside of a symbol that are marked as data (e.g. an ARM literal pool). These are
+/// displayed as a directive instead of being decoded as instructions.
+pub struct DataChunk<'a> {
+    pub addr: u64,
+    pub bytes: &'a [u8],
+}
+
+impl<'a> DataChunk<'a> {
+    pub fn directive(&self) -> &'static str {
+        match self.bytes.len() {
+            4 => ".word",
+            2 => ".short",
+            _ => ".byte",
+        }
+    }
+
+    pub fn value(&self, endian: BinaryEndian) -> u64 {
+        self.bytes.iter().enumerate().fold(0, |value, (idx, b)| {
+            let shift = match endian {
+                BinaryEndian::Little => idx * 8,
+                BinaryEndian::Big => (self.bytes.len() - idx - 1) * 8,
+            };
+            value | ((*b as u64) << shift)
+        })
+    }
+}
+
 fn write_disasm_output<'a, 'i, Out: Write + WriteColor>(
     symbol: &Symbol<'a>,
-    instrs: &'i [Insn<'i>],
+    instrs: &'i [&'i Insn<'i>],
     context: &mut DisasmContext<'a>,
     output: &mut Out,
 ) -> anyhow::Result<()> {
@@ -153,21 +356,90 @@ fn write_disasm_output<'a, 'i, Out: Write + WriteColor>(
 
     output.set_color(ColorSpec::new().set_fg(None).set_bold(false))?;
 
-    writeln!(output, "{}:", symbol.demangled_name)?;
+    writeln!(
+        output,
+        "{}:",
+        symbol.display_name_with_member(context.config.display_hashes)
+    )?;
+
+    // Data chunks are displayed in between instructions, so each instruction is pushed down by the
+    // number of chunks that come before it.
+    let mut instr_lines = Vec::with_capacity(instrs.len());
+    let mut chunks_before = 0;
+    for (instr_idx, instr) in instrs.iter().enumerate() {
+        while context
+            .data_chunks
+            .get(chunks_before)
+            .map(|chunk| chunk.addr < instr.address())
+            .unwrap_or(false)
+        {
+            chunks_before += 1;
+        }
+        instr_lines.push(instr_idx + chunks_before);
+    }
+    let line_count = instrs.len() + context.data_chunks.len();
 
     let jump_arrow_pieces = if context.config.display_jumps {
-        format::create_jump_arrows_buffer(m.jumps_width, instrs.len(), &context.jumps)
+        format::create_jump_arrows_buffer(m.jumps_width, line_count, &instr_lines, &context.jumps)
     } else {
         Vec::new()
     };
 
+    let highlighted = instrs
+        .iter()
+        .map(|instr| {
+            let instr_range = instr.address()..(instr.address() + instr.bytes().len() as u64);
+            context
+                .config
+                .highlight
+                .iter()
+                .any(|range| range.start < instr_range.end && instr_range.start < range.end)
+        })
+        .collect::<Vec<bool>>();
+    let visible = instr_visibility(&highlighted, context.config.highlight_context);
+
     let mut previous_source_path: Option<PathBuf> = None;
     let mut previous_source_line: Option<u32> = None;
+    let mut previous_inlined_function: Option<String> = None;
+    let mut next_chunk = 0;
 
     for (instr_idx, instr) in instrs.iter().enumerate() {
+        while next_chunk < instr_lines[instr_idx] - instr_idx {
+            if visible[instr_idx] {
+                write_data_chunk(
+                    &context.data_chunks[next_chunk],
+                    next_chunk + instr_idx,
+                    &m,
+                    &jump_arrow_pieces,
+                    context,
+                    output,
+                )?;
+            }
+            next_chunk += 1;
+        }
+
+        if !visible[instr_idx] {
+            // Only the first instruction of a run of hidden instructions marks the gap.
+            if instr_idx == 0 || visible[instr_idx - 1] {
+                writeln!(output, "  ...")?;
+            }
+            continue;
+        }
+
         if context.config.display_source {
             let line_mappings = &mut context.line_mappings;
             let line_cache = &mut context.line_cache;
+
+            let inlined_function = line_mappings.inlined_function(instr.address())?;
+            if inlined_function != previous_inlined_function.as_deref() {
+                previous_inlined_function = inlined_function.map(String::from);
+                if let Some(function) = inlined_function {
+                    output.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                    writeln!(output, "; inlined from {}", function)?;
+                    output.set_color(ColorSpec::new().set_fg(None))?;
+                }
+            }
+
             if let Some(line) = line_mappings
                 .get(instr.address())?
                 .filter(|(path, line)| {
@@ -197,8 +469,14 @@ fn write_disasm_output<'a, 'i, Out: Write + WriteColor>(
             }
         }
 
-        // Left padding
-        write!(output, "  ")?;
+        // Left padding, which marks highlighted instructions.
+        if highlighted[instr_idx] {
+            output.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true))?;
+            write!(output, "> ")?;
+            output.set_color(ColorSpec::new().set_fg(None).set_bold(false))?;
+        } else {
+            write!(output, "  ")?;
+        }
 
         if context.config.display_address {
             write!(
@@ -218,7 +496,7 @@ fn write_disasm_output<'a, 'i, Out: Write + WriteColor>(
                 output,
                 &jump_arrow_pieces,
                 m.jumps_width,
-                instr_idx,
+                instr_lines[instr_idx],
             )?;
         }
 
@@ -232,24 +510,104 @@ fn write_disasm_output<'a, 'i, Out: Write + WriteColor>(
             )?;
 
             output.set_color(ColorSpec::new().set_fg(None).set_bold(false))?;
-            if let (true, Some(patch)) = (
-                context.config.display_patches,
-                context.op_patches.get(instr_idx),
-            ) {
-                write!(output, "{:<width$}", patch, width = m.operands_width)?;
-            } else {
-                write!(
-                    output,
-                    "{:<width$}",
-                    instr.op_str().unwrap_or(""),
-                    width = m.operands_width
-                )?;
-            }
+            write!(
+                output,
+                "{:<width$}",
+                format::operands(instr, instr_idx, context),
+                width = m.operands_width
+            )?;
         }
 
         writeln!(output)?;
     }
 
+    // Data after the last instruction is only displayed along with it.
+    let last_visible = visible.last().copied().unwrap_or(true);
+    while last_visible && next_chunk < context.data_chunks.len() {
+        write_data_chunk(
+            &context.data_chunks[next_chunk],
+            next_chunk + instrs.len(),
+            &m,
+            &jump_arrow_pieces,
+            context,
+            output,
+        )?;
+        next_chunk += 1;
+    }
+
+    Ok(())
+}
+
+/// Returns which instructions are displayed. With `context` set, only instructions that are at
+/// most `context` instructions away from a highlighted one are displayed.
+fn instr_visibility(highlighted: &[bool], context: Option<usize>) -> Vec<bool> {
+    let context = if let Some(context) = context {
+        context
+    } else {
+        return vec![true; highlighted.len()];
+    };
+
+    (0..highlighted.len())
+        .map(|idx| {
+            let start = idx.saturating_sub(context);
+            let end = std::cmp::min(idx + context + 1, highlighted.len());
+            highlighted[start..end].iter().any(|&h| h)
+        })
+        .collect()
+}
+
+fn write_data_chunk<Out: Write + WriteColor>(
+    chunk: &DataChunk,
+    line: usize,
+    m: &format::OutputMeasure,
+    jump_arrow_pieces: &[format::ArrowPiece],
+    context: &DisasmContext,
+    output: &mut Out,
+) -> anyhow::Result<()> {
+    // Left padding
+    write!(output, "  ")?;
+
+    if context.config.display_address {
+        write!(
+            output,
+            "{:0width$x}:    ",
+            chunk.addr,
+            width = m.address_width
+        )?;
+    }
+
+    if context.config.display_bytes {
+        format::write_hex_string(chunk.bytes, m.bytes_width + 4, output)?;
+    }
+
+    if context.config.display_jumps {
+        format::write_arrow_pieces_for_line(output, jump_arrow_pieces, m.jumps_width, line)?;
+    }
+
+    if context.config.display_instr {
+        output.set_color(ColorSpec::new().set_fg(Some(Color::Blue)).set_bold(false))?;
+        write!(
+            output,
+            "{:<width$}    ",
+            chunk.directive(),
+            width = m.mnemonic_width
+        )?;
+
+        output.set_color(ColorSpec::new().set_fg(None).set_bold(false))?;
+        write!(
+            output,
+            "{:<width$}",
+            format!(
+                "0x{:0digits$x}",
+                chunk.value(context.binary.endian),
+                digits = chunk.bytes.len() * 2
+            ),
+            width = m.operands_width
+        )?;
+    }
+
+    writeln!(output)?;
+
     Ok(())
 }
 
@@ -258,13 +616,14 @@ pub struct DisasmContext<'a> {
     line_cache: FileLineCache,
     jumps: InnerJumpTable,
     op_patches: OperandPatches<'a>,
+    data_chunks: Vec<DataChunk<'a>>,
     config: DisasmConfig,
     line_mappings: LineMappings<'a>,
 }
 
 impl<'a> DisasmContext<'a> {
     pub fn new(config: DisasmConfig, binary: &'a Binary<'a>) -> anyhow::Result<DisasmContext<'a>> {
-        let line_mappings = if config.display_source {
+        let line_mappings = if config.load_debug_info {
             binary.line_mapper(&config.source_base_directory, config.source_file_resolve)?
         } else {
             crate::binary::no_op_line_mapper()
@@ -276,12 +635,77 @@ impl<'a> DisasmContext<'a> {
             line_cache: FileLineCache::new(),
             jumps: InnerJumpTable::new(),
             op_patches: OperandPatches::new(),
+            data_chunks: Vec::new(),
             line_mappings,
         })
     }
 
+    /// Highlights the instructions that were generated for `line` of the source file `file` and
+    /// returns their address ranges.
+    pub fn highlight_source_line(
+        &mut self,
+        file: &Path,
+        line: u32,
+    ) -> anyhow::Result<&[Range<u64>]> {
+        self.config.highlight = self.line_mappings.addresses_for_line(file, line)?;
+        Ok(&self.config.highlight)
+    }
+
     fn clear(&mut self) {
         self.jumps.clear();
         self.op_patches.clear();
+        self.data_chunks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(needle: &str, mode: MatchMode) -> SymbolMatcher<'_> {
+        SymbolMatcher::new(needle, mode, MatchName::Demangled, false).unwrap()
+    }
+
+    #[test]
+    fn fuzzy_matches_tokens_in_order() {
+        let m = matcher("vec::push", MatchMode::Fuzzy);
+        assert!(m.matches("alloc::vec::Vec<T>::push"));
+        assert!(m.matches("alloc::VEC::Vec<T>::PUSH"));
+        assert!(!m.matches("alloc::vec::Vec<T>::pop"));
+
+        let m = matcher("push vec", MatchMode::Fuzzy);
+        assert!(!m.matches("alloc::vec::Vec<T>::push"));
+
+        let m = matcher("ünïcode::", MatchMode::Fuzzy);
+        assert!(m.matches("crate::Ünïcode::new"));
+    }
+
+    #[test]
+    fn exact_matches_whole_name() {
+        let m = matcher("main", MatchMode::Exact);
+        assert!(m.matches("main"));
+        assert!(!m.matches("main::{{closure}}"));
+        assert!(!m.matches("Main"));
+    }
+
+    #[test]
+    fn regex_and_glob_patterns() {
+        let m = matcher(r"^core::fmt::.*::fmt$", MatchMode::Regex);
+        assert!(m.matches("core::fmt::num::fmt"));
+        assert!(!m.matches("core::fmt::num::fmt_u64"));
+
+        let m = matcher("alloc::vec::Vec<*>::push", MatchMode::Glob);
+        assert!(m.matches("alloc::vec::Vec<u8>::push"));
+        assert!(!m.matches("alloc::vec::Vec<u8>::push::{{closure}}"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_errors() {
+        for &(needle, mode) in [("(", MatchMode::Regex), ("[", MatchMode::Glob)].iter() {
+            match SymbolMatcher::new(needle, mode, MatchName::Demangled, false) {
+                Err(CargoAsmError::InvalidSearchPattern(pattern, _)) => assert_eq!(pattern, needle),
+                _ => panic!("`{}` should be an invalid pattern", needle),
+            }
+        }
     }
 }
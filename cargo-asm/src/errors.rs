@@ -1,13 +1,27 @@
 #[derive(Clone, Debug)]
 pub enum CargoAsmError {
     NoSymbolMatch(String),
+    InvalidSearchPattern(/* search string */ String, /* error */ String),
+    MultipleSymbolMatches(
+        /* search string */ String,
+        /* candidates */ Vec<String>,
+    ),
+    SymbolIndexOutOfRange(
+        /* search string */ String,
+        /* index */ usize,
+        /* matches */ usize,
+    ),
+    NoSymbolAtAddress(/* address */ u64),
+    NoCodeForLine(/* file:line */ String),
+    InvalidAddressRange(/* start */ u64, /* end */ u64),
     UnsupportedBinaryFormat(/* format */ &'static str),
-    #[allow(dead_code)]
     UnsupportedBinaryFormatOp(
         /* format */ &'static str,
         /* operation */ &'static str,
     ),
     NoCargoBinary,
+    UnsupportedArch(String),
+    NoArchMatch(/* requested */ String, /* available */ String),
 }
 
 impl std::error::Error for CargoAsmError {}
@@ -19,6 +33,52 @@ impl std::fmt::Display for CargoAsmError {
                 write!(f, "no symbol matched the search string `{}`", search_string)
             }
 
+            CargoAsmError::InvalidSearchPattern(ref search_string, ref error) => {
+                write!(f, "invalid search string `{}`: {}", search_string, error)
+            }
+
+            CargoAsmError::MultipleSymbolMatches(ref search_string, ref candidates) => {
+                write!(
+                    f,
+                    "{} symbols matched the search string `{}`, use `--index N` to pick one of them or `--all` to disassemble all of them:",
+                    candidates.len(),
+                    search_string
+                )?;
+                let index_width = (candidates.len() - 1).to_string().len();
+                for (index, candidate) in candidates.iter().enumerate() {
+                    write!(
+                        f,
+                        "\n  [{:>width$}] {}",
+                        index,
+                        candidate,
+                        width = index_width
+                    )?;
+                }
+                Ok(())
+            }
+
+            CargoAsmError::SymbolIndexOutOfRange(ref search_string, index, matches) => write!(
+                f,
+                "symbol index {} is out of range, the search string `{}` only matched {} symbols",
+                index, search_string, matches
+            ),
+
+            CargoAsmError::NoSymbolAtAddress(addr) => {
+                write!(f, "no symbol contains the address 0x{:x}", addr)
+            }
+
+            CargoAsmError::NoCodeForLine(ref line) => write!(
+                f,
+                "no code was found for `{}` in the debug information",
+                line
+            ),
+
+            CargoAsmError::InvalidAddressRange(start, end) => write!(
+                f,
+                "the address range 0x{:x}..0x{:x} isn't backed by data in the binary",
+                start, end
+            ),
+
             CargoAsmError::UnsupportedBinaryFormat(ref format) => {
                 write!(f, "binary format `{}` not supported", format)
             }
@@ -36,6 +96,16 @@ impl std::fmt::Display for CargoAsmError {
             }
 
             CargoAsmError::NoCargoBinary => write!(f, "no cargo binary found"),
+
+            CargoAsmError::UnsupportedArch(ref arch) => {
+                write!(f, "disassembling `{}` binaries is not supported", arch)
+            }
+
+            CargoAsmError::NoArchMatch(ref arch, ref available) => write!(
+                f,
+                "no architecture matched `{}` (available architectures: {})",
+                arch, available
+            ),
         }
     }
 }
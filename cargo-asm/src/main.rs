@@ -7,10 +7,11 @@ mod line_cache;
 mod platform;
 
 use anyhow::Context;
-use binary::{Binary, BinaryData, FileResolveStrategy};
-use cli::{CargoArgs, CliCommand, DisasmArgs, ListArgs};
+use binary::{Binary, BinaryData, FileResolveStrategy, Symbol, SymbolAddressIndex};
+use cli::{CargoArgs, CliCommand, DisasmArgs, DisasmTarget, ListArgs};
 use disasm::{DisasmConfig, DisasmContext};
 use errors::CargoAsmError;
+use std::io::Write as _;
 use std::path::PathBuf;
 use termcolor::{ColorChoice, StandardStream};
 
@@ -38,45 +39,72 @@ fn run_command_list(args: ListArgs) -> anyhow::Result<()> {
     let binary_bytes = std::fs::read(&binary_path)
         .with_context(|| format!("failed to read file `{}`", binary_path.to_string_lossy()))?;
     let binary_data = BinaryData::load(binary_bytes);
-    let binary = Binary::load(&binary_data, &binary_path, false)?;
-    let matcher = disasm::SymbolMatcher::new(&args.needle);
+    let binary = Binary::load(
+        &binary_data,
+        &binary_path,
+        args.arch.as_deref(),
+        args.debug_file.as_deref(),
+        false,
+    )?;
+    let matcher = disasm::SymbolMatcher::new(
+        &args.needle,
+        args.matching.mode,
+        args.matching.name,
+        args.show_hashes,
+    )?;
 
-    // First we do a measure step:
-    let mut max_addr_len = 0;
-    let mut max_size_len = 0;
-    let mut matched_any_symbols = false;
-    for symbol in binary
+    let symbols: Vec<&Symbol> = binary
         .symbols
         .iter()
-        .filter(|sym| matcher.matches(&sym.demangled_name))
-    {
-        matched_any_symbols = true;
+        .filter(|sym| matcher.matches_symbol(sym))
+        .collect();
 
-        max_addr_len = std::cmp::max(max_addr_len, disasm::format::addr_len(symbol.addr));
-        max_size_len = std::cmp::max(max_size_len, disasm::format::off_len(symbol.size));
+    if symbols.is_empty() {
+        return Err(CargoAsmError::NoSymbolMatch(matcher.needle().to_string()).into());
     }
 
-    if !matched_any_symbols {
-        return Err(CargoAsmError::NoSymbolMatch(matcher.needle().to_string()).into());
+    for line in format_symbol_list(&symbols, args.show_hashes) {
+        println!("{}", line);
     }
 
-    // Then we output:
-    for symbol in binary
-        .symbols
-        .iter()
-        .filter(|sym| matcher.matches(&sym.demangled_name))
-    {
-        println!(
-            "[address: 0x{:0addr_width$X}] [size: {:size_width$} bytes] {}",
-            symbol.addr,
-            symbol.size,
-            symbol.demangled_name,
-            addr_width = max_addr_len,
-            size_width = max_size_len,
+    Ok(())
+}
+
+/// Formats each symbol as a line with its address and size, which are aligned across the lines.
+/// The hashes of Rust symbol names are only shown if `hashes` is true.
+fn format_symbol_list(symbols: &[&Symbol], hashes: bool) -> Vec<String> {
+    // First we do a measure step:
+    let mut max_addr_len = 0;
+    let mut max_size_len = 0;
+    for symbol in symbols.iter() {
+        max_addr_len = std::cmp::max(max_addr_len, disasm::format::addr_len(symbol.addr));
+        max_size_len = std::cmp::max(
+            max_size_len,
+            disasm::format::off_len(symbol.size) + symbol.size_inferred as usize,
         );
     }
 
-    Ok(())
+    // Then we output:
+    symbols
+        .iter()
+        .map(|symbol| {
+            // Sizes that were guessed because the binary doesn't have them are marked with a `~`.
+            let size = if symbol.size_inferred {
+                format!("~{}", symbol.size)
+            } else {
+                symbol.size.to_string()
+            };
+
+            format!(
+                "[address: 0x{:0addr_width$X}] [size: {:>size_width$} bytes] {}",
+                symbol.addr,
+                size,
+                symbol.display_name_with_member(hashes),
+                addr_width = max_addr_len,
+                size_width = max_size_len,
+            )
+        })
+        .collect()
 }
 
 fn run_command_disasm(args: DisasmArgs) -> anyhow::Result<()> {
@@ -99,21 +127,17 @@ fn run_command_disasm(args: DisasmArgs) -> anyhow::Result<()> {
         binary_path = std::borrow::Cow::from(get_cargo_binary_path(&args.cargo)?);
     };
 
-    let binary_bytes = std::fs::read(&binary_path)
-        .with_context(|| format!("failed to read file `{}`", binary_path.to_string_lossy()))?;
-    let binary_data = BinaryData::load(binary_bytes);
-    let binary = Binary::load(&binary_data, &binary_path, false)?;
-    let matcher = disasm::SymbolMatcher::new(&args.needle);
-
     let mut config = DisasmConfig::default();
     config.display_address = args.show_addrs;
     config.display_bytes = args.show_bytes;
     config.display_jumps = args.show_jumps;
     config.display_patches = true;
+    config.display_hashes = args.show_hashes;
     config.display_instr = true;
     config.display_source = args.show_source;
     config.load_debug_info = args.show_source;
-    config.source_base_directory = args.source_root.unwrap_or(source_root);
+    config.syntax = args.syntax;
+    config.source_base_directory = args.source_root.clone().unwrap_or(source_root);
     config.source_file_resolve = if args.absolute_source_path {
         FileResolveStrategy::PreferAbsolute
     } else {
@@ -124,14 +148,80 @@ fn run_command_disasm(args: DisasmArgs) -> anyhow::Result<()> {
     //       of instructions.
     config.display_length = true;
     config.display_instr_count = true;
+    config.debug_file = args.debug_file.clone();
 
-    let matched_symbol = binary
-        .symbols
-        .iter()
-        .find(|sym| matcher.matches(&sym.demangled_name))
-        .ok_or_else(|| CargoAsmError::NoSymbolMatch(matcher.needle().to_string()))?;
+    match args.target {
+        DisasmTarget::Address(addr) => {
+            config.highlight.push(addr..addr.saturating_add(1));
+            config.highlight_context = Some(args.context);
+        }
+
+        // The line tables are needed to find the code for the line.
+        DisasmTarget::Line(..) => config.load_debug_info = true,
+
+        _ => {}
+    }
+
+    let binary_bytes = std::fs::read(&binary_path)
+        .with_context(|| format!("failed to read file `{}`", binary_path.to_string_lossy()))?;
+    let binary_data = BinaryData::load(binary_bytes);
+    let binary = Binary::load(
+        &binary_data,
+        &binary_path,
+        args.arch.as_deref(),
+        config.debug_file.as_deref(),
+        false,
+    )?;
     let mut context = DisasmContext::new(config, &binary)?;
 
+    // Only used when disassembling a range of addresses.
+    let range_symbol;
+    let matched_symbols: Vec<&Symbol> = match args.target {
+        DisasmTarget::Search(ref needle) => search_symbols(&binary, needle, &args)?,
+
+        DisasmTarget::Address(addr) => {
+            let index = SymbolAddressIndex::new(&binary.symbols);
+            vec![index
+                .containing(addr)
+                .ok_or(CargoAsmError::NoSymbolAtAddress(addr))?]
+        }
+
+        DisasmTarget::Range(ref range) => {
+            range_symbol = binary
+                .range_symbol(range.clone())
+                .ok_or(CargoAsmError::InvalidAddressRange(range.start, range.end))?;
+            vec![&range_symbol]
+        }
+
+        DisasmTarget::Line(ref file, line) => {
+            let ranges = context.highlight_source_line(file, line)?;
+            if ranges.is_empty() {
+                return Err(
+                    CargoAsmError::NoCodeForLine(format!("{}:{}", file.display(), line)).into(),
+                );
+            }
+
+            // Code for a line can be spread across several symbols if it was inlined or is
+            // generic, each one is shown once.
+            let index = SymbolAddressIndex::new(&binary.symbols);
+            let mut symbols: Vec<&Symbol> = Vec::new();
+            for symbol in ranges
+                .iter()
+                .filter_map(|range| index.containing(range.start))
+            {
+                if !symbols.iter().any(|s| s.addr == symbol.addr) {
+                    symbols.push(symbol);
+                }
+            }
+
+            if symbols.is_empty() {
+                return Err(CargoAsmError::NoSymbolAtAddress(ranges[0].start).into());
+            }
+            symbols.sort_by_key(|symbol| symbol.addr);
+            symbols
+        }
+    };
+
     let color_choice = if args.disable_color {
         ColorChoice::Never
     } else {
@@ -143,11 +233,66 @@ fn run_command_disasm(args: DisasmArgs) -> anyhow::Result<()> {
     };
 
     let mut stdout = StandardStream::stdout(color_choice);
-    disasm::disassemble(matched_symbol, &mut context, &mut stdout)?;
+    for (idx, matched_symbol) in matched_symbols.iter().enumerate() {
+        if idx > 0 {
+            writeln!(stdout)?;
+        }
+        disasm::disassemble(matched_symbol, &mut context, &mut stdout)?;
+    }
 
     Ok(())
 }
 
+/// Returns the symbols whose names match `needle`. Several matches are only returned if `--all` is
+/// passed, otherwise `--index` picks one of them.
+fn search_symbols<'b, 'a>(
+    binary: &'b Binary<'a>,
+    needle: &str,
+    args: &DisasmArgs,
+) -> anyhow::Result<Vec<&'b Symbol<'a>>> {
+    let matcher = disasm::SymbolMatcher::new(
+        needle,
+        args.matching.mode,
+        args.matching.name,
+        args.show_hashes,
+    )?;
+
+    // Symbols with the same address (e.g. functions merged by the linker) have the same code, so
+    // only the first one of them is a candidate.
+    let mut matched_addresses = std::collections::HashSet::new();
+    let matched_symbols: Vec<&Symbol> = binary
+        .symbols
+        .iter()
+        .filter(|sym| matcher.matches_symbol(sym))
+        .filter(|sym| matched_addresses.insert(sym.addr))
+        .collect();
+
+    let matched_symbols = match (args.index, args.all, matched_symbols.len()) {
+        (_, _, 0) => {
+            return Err(CargoAsmError::NoSymbolMatch(matcher.needle().to_string()).into());
+        }
+        (Some(index), _, count) if index >= count => {
+            return Err(CargoAsmError::SymbolIndexOutOfRange(
+                matcher.needle().to_string(),
+                index,
+                count,
+            )
+            .into());
+        }
+        (Some(index), _, _) => &matched_symbols[index..=index],
+        (None, true, _) | (None, false, 1) => &matched_symbols[..],
+        (None, false, _) => {
+            return Err(CargoAsmError::MultipleSymbolMatches(
+                matcher.needle().to_string(),
+                format_symbol_list(&matched_symbols, args.show_hashes),
+            )
+            .into());
+        }
+    };
+
+    Ok(matched_symbols.to_vec())
+}
+
 fn get_cargo_binary_path(cargo_args: &CargoArgs) -> anyhow::Result<PathBuf> {
     use cargo_metadata::Target;
 